@@ -1,2 +1,2 @@
 #!/bin/bash
-sudo -u root -g asus-control ./asus-control-daemon
\ No newline at end of file
+sudo -u root -g asus-control ./asus-control-daemon --foreground
\ No newline at end of file
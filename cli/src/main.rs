@@ -1,7 +1,45 @@
 use std::env;
+use std::fmt;
 use std::io::{self, Read, Write};
 use std::os::unix::net::UnixStream;
 
+use asus_control_protocol::{DaemonInfo, PROTOCOL_VERSION, hello, query, socket_path};
+
+/// Checks every line of `cmd` against what the daemon announced.
+fn check(info: &DaemonInfo, cmd: &str) -> Result<(), String> {
+    let too_old = |what: &str| {
+        Err(format!(
+            "Daemon too old: {} does not support '{}', please update it",
+            info.daemon, what
+        ))
+    };
+    if info.protocol > 0 && info.protocol < PROTOCOL_VERSION {
+        return too_old(&format!("protocol {}", PROTOCOL_VERSION));
+    }
+
+    for line in cmd.lines() {
+        let mut tokens = line.split_whitespace().filter(|t| *t != "--dry-run");
+        let verb = tokens.next().unwrap_or("");
+        if verb == "simulate" && !info.verbs.iter().any(|v| v == verb) {
+            return Err("Daemon is not running with --simulate".into());
+        }
+        if !info.verbs.iter().any(|v| v == verb) {
+            return too_old(verb);
+        }
+        let targets = match verb {
+            "get" => &info.get,
+            "set" => &info.set,
+            _ => continue,
+        };
+        if let Some(target) = tokens.next()
+            && !targets.iter().any(|t| t == target)
+        {
+            return too_old(&format!("{} {}", verb, target));
+        }
+    }
+    Ok(())
+}
+
 enum PlatformProfile {
     Quiet,
     Balanced,
@@ -9,6 +47,15 @@ enum PlatformProfile {
 }
 
 impl PlatformProfile {
+    fn parse(s: &str) -> Option<PlatformProfile> {
+        match s {
+            "quiet" => Some(PlatformProfile::Quiet),
+            "balanced" => Some(PlatformProfile::Balanced),
+            "performance" => Some(PlatformProfile::Performance),
+            _ => None,
+        }
+    }
+
     fn as_str(&self) -> &str {
         match self {
             PlatformProfile::Quiet => "quiet",
@@ -24,6 +71,39 @@ enum Command {
     GetBatteryThreshold,
     GetProfile,
     GetFanSpeedRpm,
+    GetProfileTrigger,
+    GetTemps,
+    GetFans,
+    SetFanCurve {
+        fan: String,
+        curve: String,
+        profile: Option<PlatformProfile>,
+    },
+    GetFanCurve {
+        fan: String,
+        profile: Option<PlatformProfile>,
+    },
+    GetFirmwareAttrs,
+    SetFirmwareAttr {
+        name: String,
+        value: String,
+    },
+    GetCapabilities,
+    GetSystemInfo,
+    GetCpuTuning,
+    GetProfileDetails,
+    SetKeyboardBacklight(u32),
+    GetKeyboardBacklight,
+    GetPresets,
+    ApplyPreset(String),
+    SavePreset(String),
+    DeletePreset(String),
+    Batch(Vec<Command>),
+    Status,
+    Hello,
+    GetSensorHistory,
+    Subscribe,
+    SimulateAc(String),
 }
 
 impl Command {
@@ -34,58 +114,303 @@ impl Command {
             "get profile",
             "get battery-threshold",
             "get fan-speed-rpm",
+            "get profile-trigger",
+            "get temps",
+            "get fans",
+            "set fan-curve <cpu|gpu|mid> <temp:percent,... (8 points)|default> [profile]",
+            "get fan-curve <cpu|gpu|mid> [profile]",
+            "get firmware-attrs",
+            "set firmware-attr <name> <value>",
+            "get capabilities",
+            "get system-info",
+            "get cpu-tuning",
+            "get profile-details",
+            "set keyboard-backlight <num>",
+            "get keyboard-backlight",
+            "get presets",
+            "apply preset <name>",
+            "save preset <name>",
+            "delete preset <name>",
+            "set <target> <value> set <target> <value> ...",
+            "status",
+            "get status",
+            "hello",
+            "get sensor-history",
+            "subscribe",
+            "simulate ac <online|offline|toggle>",
         ]
     }
 
     fn parse(input: &str) -> Result<Command, ()> {
+        let clauses = split_set_clauses(input);
+        if clauses.len() > 1 {
+            return clauses
+                .iter()
+                .map(|c| Command::parse_single(c))
+                .collect::<Result<Vec<_>, ()>>()
+                .map(Command::Batch);
+        }
+        Command::parse_single(input)
+    }
+
+    fn parse_single(input: &str) -> Result<Command, ()> {
         let mut parts = input.split_whitespace();
         let verb = parts.next().unwrap_or("");
 
         match verb {
             "set" => match parts.next() {
                 Some("battery-threshold") => {
-                    if let Some(nstr) = parts.next() {
-                        if let Ok(n) = nstr.parse::<i32>() {
-                            return Ok(Command::SetBatteryThreshold(n));
-                        }
+                    if let Some(nstr) = parts.next()
+                        && let Ok(n) = nstr.parse::<i32>()
+                    {
+                        return Ok(Command::SetBatteryThreshold(n));
                     }
                     Err(())
                 }
                 Some("profile") => {
-                    if let Some(arg) = parts.next() {
-                        let profile = match arg {
-                            "quiet" => PlatformProfile::Quiet,
-                            "balanced" => PlatformProfile::Balanced,
-                            "performance" => PlatformProfile::Performance,
-                            _ => return Err(()),
-                        };
+                    if let Some(profile) = parts.next().and_then(PlatformProfile::parse) {
                         return Ok(Command::SetProfile(profile));
                     }
                     Err(())
                 }
+                Some("fan-curve") => {
+                    let fan = parse_fan(parts.next())?;
+                    let curve = parts.next().ok_or(())?.to_string();
+                    let profile = parse_optional_profile(parts.next())?;
+                    Ok(Command::SetFanCurve {
+                        fan,
+                        curve,
+                        profile,
+                    })
+                }
+                Some("keyboard-backlight") => {
+                    let n = parts.next().ok_or(())?.parse::<u32>().map_err(|_| ())?;
+                    Ok(Command::SetKeyboardBacklight(n))
+                }
+                Some("firmware-attr") => {
+                    let name = parts.next().ok_or(())?.to_string();
+                    let value = parts.collect::<Vec<_>>().join(" ");
+                    if value.is_empty() {
+                        return Err(());
+                    }
+                    Ok(Command::SetFirmwareAttr { name, value })
+                }
                 _ => Err(()),
             },
             "get" => match parts.next() {
                 Some("battery-threshold") => Ok(Command::GetBatteryThreshold),
                 Some("profile") => Ok(Command::GetProfile),
                 Some("fan-speed-rpm") => Ok(Command::GetFanSpeedRpm),
+                Some("profile-trigger") => Ok(Command::GetProfileTrigger),
+                Some("temps") => Ok(Command::GetTemps),
+                Some("fans") => Ok(Command::GetFans),
+                Some("fan-curve") => {
+                    let fan = parse_fan(parts.next())?;
+                    let profile = parse_optional_profile(parts.next())?;
+                    Ok(Command::GetFanCurve { fan, profile })
+                }
+                Some("firmware-attrs") => Ok(Command::GetFirmwareAttrs),
+                Some("capabilities") => Ok(Command::GetCapabilities),
+                Some("system-info") => Ok(Command::GetSystemInfo),
+                Some("cpu-tuning") => Ok(Command::GetCpuTuning),
+                Some("profile-details") => Ok(Command::GetProfileDetails),
+                Some("keyboard-backlight") => Ok(Command::GetKeyboardBacklight),
+                Some("presets") => Ok(Command::GetPresets),
+                Some("sensor-history") => Ok(Command::GetSensorHistory),
+                Some("status") => Ok(Command::Status),
+                _ => Err(()),
+            },
+            "status" => Ok(Command::Status),
+            "hello" => Ok(Command::Hello),
+            "subscribe" => Ok(Command::Subscribe),
+            "simulate" => match (parts.next(), parts.next()) {
+                (Some("ac"), Some(state @ ("online" | "offline" | "toggle"))) => {
+                    Ok(Command::SimulateAc(state.to_string()))
+                }
+                _ => Err(()),
+            },
+            "apply" | "save" | "delete" => match (parts.next(), parts.next()) {
+                (Some("preset"), Some(name)) => {
+                    let name = name.to_string();
+                    Ok(match verb {
+                        "apply" => Command::ApplyPreset(name),
+                        "save" => Command::SavePreset(name),
+                        _ => Command::DeletePreset(name),
+                    })
+                }
                 _ => Err(()),
             },
             _ => Err(()),
         }
     }
+}
 
-    fn to_string(&self) -> String {
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Command::SetBatteryThreshold(n) => format!("set battery-threshold {}", n),
-            Command::SetProfile(profile) => format!("set profile {}", profile.as_str()),
-            Command::GetBatteryThreshold => "get battery-threshold".into(),
-            Command::GetProfile => "get profile".into(),
-            Command::GetFanSpeedRpm => "get fan-speed-rpm".into(),
+            Command::SetBatteryThreshold(n) => write!(f, "set battery-threshold {}", n),
+            Command::SetProfile(profile) => write!(f, "set profile {}", profile.as_str()),
+            Command::GetBatteryThreshold => write!(f, "get battery-threshold"),
+            Command::GetProfile => write!(f, "get profile"),
+            Command::GetFanSpeedRpm => write!(f, "get fan-speed-rpm"),
+            Command::GetProfileTrigger => write!(f, "get profile-trigger"),
+            Command::GetTemps => write!(f, "get temps"),
+            Command::GetFans => write!(f, "get fans"),
+            Command::SetFanCurve {
+                fan,
+                curve,
+                profile,
+            } => {
+                write!(f, "set fan-curve {} {}", fan, curve)?;
+                if let Some(p) = profile {
+                    write!(f, " {}", p.as_str())?;
+                }
+                Ok(())
+            }
+            Command::GetFanCurve { fan, profile } => {
+                write!(f, "get fan-curve {}", fan)?;
+                if let Some(p) = profile {
+                    write!(f, " {}", p.as_str())?;
+                }
+                Ok(())
+            }
+            Command::GetFirmwareAttrs => write!(f, "get firmware-attrs"),
+            Command::SetFirmwareAttr { name, value } => {
+                write!(f, "set firmware-attr {} {}", name, value)
+            }
+            Command::GetCapabilities => write!(f, "get capabilities"),
+            Command::GetSystemInfo => write!(f, "get system-info"),
+            Command::GetCpuTuning => write!(f, "get cpu-tuning"),
+            Command::GetProfileDetails => write!(f, "get profile-details"),
+            Command::SetKeyboardBacklight(n) => write!(f, "set keyboard-backlight {}", n),
+            Command::GetKeyboardBacklight => write!(f, "get keyboard-backlight"),
+            Command::GetPresets => write!(f, "get presets"),
+            Command::ApplyPreset(name) => write!(f, "apply preset {}", name),
+            Command::SavePreset(name) => write!(f, "save preset {}", name),
+            Command::DeletePreset(name) => write!(f, "delete preset {}", name),
+            Command::Status => write!(f, "get status"),
+            Command::Hello => write!(f, "hello"),
+            Command::GetSensorHistory => write!(f, "get sensor-history"),
+            Command::Subscribe => write!(f, "subscribe"),
+            Command::SimulateAc(state) => write!(f, "simulate ac {}", state),
+            Command::Batch(commands) => {
+                write!(f, "batch")?;
+                for c in commands {
+                    write!(f, "\n{}", c)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
+/// Splits `set a 1 set b 2` into one clause per `set`; other input stays whole.
+fn split_set_clauses(input: &str) -> Vec<String> {
+    let mut clauses: Vec<Vec<&str>> = Vec::new();
+    for token in input.split_whitespace() {
+        match clauses.last_mut() {
+            Some(clause) if token != "set" => clause.push(token),
+            _ => clauses.push(vec![token]),
+        }
+    }
+    if clauses.first().is_some_and(|c| c[0] == "set") {
+        clauses.into_iter().map(|c| c.join(" ")).collect()
+    } else {
+        vec![input.to_string()]
+    }
+}
+
+/// Renders a `get status` response as an aligned two-column table.
+fn render_status(response: &str) -> String {
+    let rows: Vec<(String, String)> = response
+        .lines()
+        .filter_map(|l| l.split_once(": "))
+        .map(|(key, value)| {
+            let (label, unit) = match key {
+                "profile" => ("Profile".to_string(), ""),
+                "backend" => ("Profile backend".into(), ""),
+                "choices" => ("Profile choices".into(), ""),
+                "battery-threshold" => ("Charge limit".into(), "%"),
+                "charge-start-threshold" => ("Charge start".into(), "%"),
+                "battery-capacity" => ("Battery".into(), "%"),
+                "battery-status" => ("Battery status".into(), ""),
+                "battery-power" => ("Battery power".into(), " W"),
+                "ac" => ("AC".into(), ""),
+                "cpu-temp" => ("CPU temperature".into(), " °C"),
+                "keyboard-backlight" => ("Keyboard backlight".into(), ""),
+                _ => match (key.strip_prefix("fan."), key.strip_prefix("temp.")) {
+                    (Some(fan), _) => (format!("Fan {}", fan), " RPM"),
+                    (_, Some(sensor)) => (sensor.to_string(), " °C"),
+                    _ => (key.to_string(), ""),
+                },
+            };
+            let value = match value {
+                "unavailable" => "-".to_string(),
+                v => format!("{}{}", v, unit),
+            };
+            (label, value)
+        })
+        .collect();
+
+    let width = rows
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    rows.iter()
+        .map(|(label, value)| format!("{:width$}  {}", label, value, width = width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn parse_fan(arg: Option<&str>) -> Result<String, ()> {
+    match arg {
+        Some(fan @ ("cpu" | "gpu" | "mid")) => Ok(fan.to_string()),
+        _ => Err(()),
+    }
+}
+
+fn parse_optional_profile(arg: Option<&str>) -> Result<Option<PlatformProfile>, ()> {
+    match arg {
+        Some(a) => PlatformProfile::parse(a).map(Some).ok_or(()),
+        None => Ok(None),
+    }
+}
+
+/// Maps a get/set target to the `get capabilities` feature it needs.
+fn feature_of(target: &str) -> Option<&'static str> {
+    match target {
+        "battery-threshold" => Some("battery-threshold"),
+        "profile" => Some("profile"),
+        "fans" | "fan-speed-rpm" => Some("fans"),
+        "fan-curve" => Some("fan-curves"),
+        "keyboard-backlight" => Some("keyboard-backlight"),
+        "firmware-attr" | "firmware-attrs" => Some("firmware-attributes"),
+        _ => None,
+    }
+}
+
+/// Features the daemon reports as unavailable; empty if it cannot be asked.
+fn unavailable_features() -> Vec<String> {
+    let Ok(caps) = query("get capabilities") else {
+        return Vec::new();
+    };
+    caps.lines()
+        .filter_map(|l| l.split_once(": "))
+        .filter(|(_, v)| *v == "unavailable")
+        .map(|(k, _)| k.to_string())
+        .collect()
+}
+
+/// Prints sensor updates as the daemon pushes them, until it closes the connection.
+fn subscribe() -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.write_all(b"subscribe")?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    io::copy(&mut stream, &mut io::stdout())?;
+    Ok(())
+}
+
 fn main() -> io::Result<()> {
     let input = {
         let args = env::args().skip(1).collect::<Vec<_>>();
@@ -101,9 +426,17 @@ fn main() -> io::Result<()> {
         }
     };
 
+    let dry_run = input.split_whitespace().any(|t| t == "--dry-run");
+    let input = input
+        .split_whitespace()
+        .filter(|t| *t != "--dry-run")
+        .collect::<Vec<_>>()
+        .join(" ");
+
     if input.is_empty() {
         eprintln!(
-            "Usage: {} <command> or provide command on stdin",
+            "Usage: {} [--dry-run] <command> or provide command on stdin\n\
+             Set ASUS_CONTROL_SOCKET to reach a daemon listening elsewhere",
             env::args().next().unwrap_or_else(|| "asus-control".into())
         );
         std::process::exit(2);
@@ -113,19 +446,60 @@ fn main() -> io::Result<()> {
         Ok(c) => c,
         Err(_) => {
             let first_token = input.split_whitespace().next().unwrap_or("");
+            let unavailable = unavailable_features();
+            let valid = Command::variants()
+                .iter()
+                .filter(|v| {
+                    v.split_whitespace()
+                        .nth(1)
+                        .and_then(feature_of)
+                        .is_none_or(|f| !unavailable.iter().any(|u| u == f))
+                })
+                .copied()
+                .collect::<Vec<_>>();
             eprintln!("Invalid command: {}", first_token);
-            eprintln!("Valid commands: {}", Command::variants().join(", "));
+            eprintln!("Valid commands: {}", valid.join(", "));
             std::process::exit(2);
         }
     };
 
-    let mut stream = UnixStream::connect("/run/asus-control-daemon.sock")?;
-    stream.write_all(cmd.to_string().as_bytes())?;
-    stream.shutdown(std::net::Shutdown::Write)?;
+    let cmd = if dry_run {
+        match cmd.to_string().split_once(' ') {
+            Some((verb @ ("set" | "apply" | "save" | "delete"), rest)) => {
+                format!("{} --dry-run {}", verb, rest)
+            }
+            _ if matches!(cmd, Command::Batch(_)) => {
+                cmd.to_string().replacen("batch", "batch --dry-run", 1)
+            }
+            _ => {
+                eprintln!("--dry-run only applies to commands that change settings");
+                std::process::exit(2);
+            }
+        }
+    } else {
+        cmd.to_string()
+    };
+    if let Err(e) = check(&hello()?, &cmd) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    if let Some(feature) = cmd.split_whitespace().nth(1).and_then(feature_of)
+        && unavailable_features().iter().any(|u| u == feature)
+    {
+        eprintln!("Not supported on this machine: {}", feature);
+        std::process::exit(1);
+    }
+
+    if cmd == "subscribe" {
+        return subscribe();
+    }
 
-    let mut response = String::new();
-    stream.read_to_string(&mut response)?;
-    println!("{}", response);
+    let response = query(&cmd)?;
+    if cmd == "get status" && !response.starts_with("error:") {
+        println!("{}", render_status(&response));
+    } else {
+        println!("{}", response);
+    }
 
     Ok(())
 }
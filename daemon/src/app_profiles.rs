@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::config::{AppProfilesConfig, AppRule};
+use crate::profile::{PlatformProfile, get_fan_profile, set_fan_mode};
+use crate::transaction;
+
+/// The process that caused the current profile switch.
+struct Trigger {
+    pid: u32,
+    name: String,
+    profile: PlatformProfile,
+    previous: Option<PlatformProfile>,
+}
+
+static ACTIVE: Mutex<Option<Trigger>> = Mutex::new(None);
+
+/// Profile switches made by rules, including restores when a process exits.
+pub static SWITCHES: AtomicU64 = AtomicU64::new(0);
+
+pub fn spawn(config: &'static AppProfilesConfig) {
+    if config.rule.is_empty() {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.interval.max(1));
+    thread::spawn(move || {
+        loop {
+            scan(&config.rule);
+            thread::sleep(interval);
+        }
+    });
+}
+
+pub fn current_trigger() -> String {
+    match &*ACTIVE.lock().unwrap() {
+        Some(t) => format!("{} (pid {}) -> {}", t.name, t.pid, t.profile.as_str()),
+        None => "none".into(),
+    }
+}
+
+fn scan(rules: &[AppRule]) {
+    let found = find_match(rules);
+    let _settings = transaction::lock();
+    let mut active = ACTIVE.lock().unwrap();
+
+    match (found, active.take()) {
+        (Some((pid, name, profile)), Some(t)) => {
+            if profile != t.profile {
+                apply(profile, &name);
+            }
+            *active = Some(Trigger {
+                pid,
+                name,
+                profile,
+                previous: t.previous,
+            });
+        }
+        (Some((pid, name, profile)), None) => {
+            let previous = get_fan_profile()
+                .ok()
+                .and_then(|s| PlatformProfile::parse(&s));
+            apply(profile, &name);
+            *active = Some(Trigger {
+                pid,
+                name,
+                profile,
+                previous,
+            });
+        }
+        (None, Some(t)) => {
+            // A profile chosen since the rule switched is the user's to keep.
+            let current = get_fan_profile()
+                .ok()
+                .and_then(|s| PlatformProfile::parse(&s));
+            if current != Some(t.profile) {
+                info!("{} exited, keeping the profile chosen since", t.name);
+            } else if let Some(p) = t.previous {
+                info!("{} exited, restoring profile {}", t.name, p.as_str());
+                match set_fan_mode(p) {
+                    Ok(_) => {
+                        SWITCHES.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => warn!("Failed to restore profile: {}", e),
+                }
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+fn apply(profile: PlatformProfile, name: &str) {
+    info!(
+        "{} is running, switching to profile {}",
+        name,
+        profile.as_str()
+    );
+    match set_fan_mode(profile) {
+        Ok(_) => {
+            SWITCHES.fetch_add(1, Ordering::Relaxed);
+        }
+        Err(e) => warn!("Failed to switch profile: {}", e),
+    }
+}
+
+/// Returns the first running process matching a rule, in rule order.
+fn find_match(rules: &[AppRule]) -> Option<(u32, String, PlatformProfile)> {
+    let procs = running_processes();
+    for rule in rules {
+        for (pid, comm, exe) in &procs {
+            if rule.executables.iter().any(|e| matches(e, comm, exe)) {
+                return Some((*pid, comm.clone(), rule.profile));
+            }
+        }
+    }
+    None
+}
+
+/// Whether a process named `comm` running `exe` is `executable`: a path matches the whole
+/// executable path, a bare name the process name or the executable's file name.
+fn matches(executable: &str, comm: &str, exe: &str) -> bool {
+    if executable.contains('/') {
+        exe == executable
+    } else {
+        comm == executable || Path::new(exe).file_name().is_some_and(|f| f == executable)
+    }
+}
+
+fn running_processes() -> Vec<(u32, String, String)> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let exe = fs::read_link(entry.path().join("exe"))
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Some((pid, comm.trim().to_string(), exe))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_executables() {
+        let cases = [
+            ("blender", "blender", "/usr/bin/blender", true),
+            ("blender", "blender", "", true),
+            ("steam", "steamwebhelper", "/usr/lib/steam/steam", true),
+            (
+                "steam",
+                "steamwebhelper",
+                "/usr/lib/steam/steamwebhelper",
+                false,
+            ),
+            ("/usr/bin/blender", "blender", "/usr/bin/blender", true),
+            ("/usr/bin/blender", "blender", "/opt/blender/blender", false),
+            ("/usr/bin/blender", "blender", "", false),
+            ("bin/blender", "blender", "/usr/bin/blender", false),
+            ("blend", "blender", "/usr/bin/blender", false),
+        ];
+        for (executable, comm, exe, expected) in cases {
+            assert_eq!(
+                matches(executable, comm, exe),
+                expected,
+                "{:?} against {:?} ({:?})",
+                executable,
+                comm,
+                exe
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::message::Header;
+
+use crate::backend;
+use crate::dbus;
+use crate::keyboard;
+use crate::profile::{PlatformProfile, get_fan_profile, set_fan_mode};
+use crate::transaction;
+use crate::{get_battery_threshold, set_battery_threshold};
+
+pub const BUS_NAME: &str = "xyz.ljones.Asusd";
+const PLATFORM_PATH: &str = "/xyz/ljones";
+const AURA_PATH: &str = "/xyz/ljones/aura/kbd_backlight";
+
+/// How often the served values are checked for changes made through the socket or by rules.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// asusd's numbering of platform profiles.
+const PROFILES: &[(u32, PlatformProfile)] = &[
+    (0, PlatformProfile::Balanced),
+    (1, PlatformProfile::Performance),
+    (2, PlatformProfile::Quiet),
+];
+
+fn not_supported(what: &str) -> fdo::Error {
+    fdo::Error::NotSupported(format!("{} is not supported by asus-control-daemon", what))
+}
+
+fn active_profile() -> fdo::Result<PlatformProfile> {
+    let name = get_fan_profile().map_err(fdo::Error::Failed)?;
+    PlatformProfile::parse(&name)
+        .ok_or_else(|| fdo::Error::Failed(format!("unsupported active profile: {}", name)))
+}
+
+fn set_profile(profile: PlatformProfile) -> fdo::Result<()> {
+    set_fan_mode(profile)
+        .map(|_| ())
+        .map_err(fdo::Error::Failed)
+}
+
+/// The subset of asusd's `xyz.ljones.Platform` this daemon can back. Members left out get
+/// the bus's unknown method or property errors.
+struct Platform;
+
+#[zbus::interface(name = "xyz.ljones.Platform")]
+impl Platform {
+    #[zbus(property)]
+    fn platform_profile(&self) -> fdo::Result<u32> {
+        let active = active_profile()?;
+        Ok(PROFILES.iter().find(|(_, p)| *p == active).unwrap().0)
+    }
+
+    #[zbus(property)]
+    async fn set_platform_profile(
+        &mut self,
+        value: u32,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        dbus::authorize(conn, header.as_ref()).await?;
+        let profile = PROFILES
+            .iter()
+            .find(|(n, _)| *n == value)
+            .map(|(_, p)| *p)
+            .filter(|p| backend::get().profile_choices().contains(p))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("unsupported profile: {}", value)))?;
+        set_profile(profile)
+    }
+
+    #[zbus(property)]
+    fn platform_profile_choices(&self) -> Vec<u32> {
+        let choices = backend::get().profile_choices();
+        PROFILES
+            .iter()
+            .filter(|(_, p)| choices.contains(p))
+            .map(|(n, _)| *n)
+            .collect()
+    }
+
+    /// Moves to the next supported profile, wrapping around.
+    async fn next_platform_profile(
+        &self,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<()> {
+        dbus::authorize(conn, Some(&header)).await?;
+        let _settings = transaction::lock();
+        let choices = backend::get().profile_choices();
+        let active = active_profile()?;
+        let next = match choices.iter().position(|p| *p == active) {
+            Some(i) => choices[(i + 1) % choices.len()],
+            None => *choices
+                .first()
+                .ok_or_else(|| not_supported("platform profile"))?,
+        };
+        set_profile(next)
+    }
+
+    #[zbus(property)]
+    fn charge_control_end_threshold(&self) -> fdo::Result<u8> {
+        get_battery_threshold()
+            .map_err(fdo::Error::Failed)?
+            .parse()
+            .map_err(|_| fdo::Error::Failed("invalid battery threshold".into()))
+    }
+
+    #[zbus(property)]
+    async fn set_charge_control_end_threshold(
+        &mut self,
+        value: u8,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        dbus::authorize(conn, header.as_ref()).await?;
+        set_battery_threshold(value as i32)
+            .map(|_| ())
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    fn one_shot_full_charge(&self) -> fdo::Result<()> {
+        Err(not_supported("OneShotFullCharge"))
+    }
+}
+
+/// The keyboard backlight part of asusd's `xyz.ljones.Aura`. Levels are asusd's
+/// off, low, med and high, i.e. 0 to 3.
+struct Aura;
+
+#[zbus::interface(name = "xyz.ljones.Aura")]
+impl Aura {
+    #[zbus(property)]
+    fn brightness(&self) -> fdo::Result<u32> {
+        backend::get()
+            .keyboard_brightness()
+            .map_err(fdo::Error::Failed)
+    }
+
+    #[zbus(property)]
+    async fn set_brightness(
+        &mut self,
+        value: u32,
+        #[zbus(connection)] conn: &zbus::Connection,
+        #[zbus(header)] header: Option<Header<'_>>,
+    ) -> fdo::Result<()> {
+        dbus::authorize(conn, header.as_ref()).await?;
+        keyboard::set_keyboard_backlight(value)
+            .map(|_| ())
+            .map_err(fdo::Error::InvalidArgs)
+    }
+
+    #[zbus(property)]
+    fn supported_brightness(&self) -> fdo::Result<Vec<u32>> {
+        let max = backend::get()
+            .max_keyboard_brightness()
+            .map_err(fdo::Error::Failed)?;
+        Ok((0..=max.min(3)).collect())
+    }
+}
+
+/// Announces values that changed since the last poll.
+fn notify(conn: Connection) {
+    let read = || {
+        (
+            get_fan_profile().ok(),
+            get_battery_threshold().ok(),
+            keyboard::get_keyboard_backlight().ok(),
+        )
+    };
+    let mut last = read();
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = read();
+        if current == last {
+            continue;
+        }
+
+        let result = (|| -> zbus::Result<()> {
+            let server = conn.object_server();
+            let platform = server.interface::<_, Platform>(PLATFORM_PATH)?;
+            let aura = server.interface::<_, Aura>(AURA_PATH)?;
+            let (platform, platform_emitter) = (platform.get(), platform.signal_emitter());
+            let (aura, aura_emitter) = (aura.get(), aura.signal_emitter());
+            zbus::block_on(async {
+                if current.0 != last.0 {
+                    platform.platform_profile_changed(platform_emitter).await?;
+                }
+                if current.1 != last.1 {
+                    platform
+                        .charge_control_end_threshold_changed(platform_emitter)
+                        .await?;
+                }
+                if current.2 != last.2 {
+                    aura.brightness_changed(aura_emitter).await?;
+                }
+                Ok(())
+            })
+        })();
+        if let Err(e) = result {
+            warn!("Failed to announce asusd property change: {}", e);
+        }
+        last = current;
+    }
+}
+
+/// Serves the platform and keyboard interfaces, plus the object manager asusd clients use
+/// to find them, on `conn`.
+pub fn serve(conn: &Connection) -> zbus::Result<()> {
+    let server = conn.object_server();
+    server.at("/", fdo::ObjectManager)?;
+    server.at(PLATFORM_PATH, Platform)?;
+    server.at(AURA_PATH, Aura)?;
+
+    let notifier = conn.clone();
+    thread::spawn(move || notify(notifier));
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use std::path::{Path, PathBuf};
+#[cfg(test)]
+use std::sync::{Mutex, OnceLock};
+
+use crate::keyboard::KBD_BACKLIGHT_DIR;
+use crate::profile::{PlatformProfile, ProfileBackend};
+use crate::quirks;
+use crate::sensors::{self, FanReading, channels, hwmon_devices, read_trimmed};
+use crate::sysfs;
+
+/// The hardware the command layer drives: platform profile, battery, fans and keyboard LEDs.
+/// Range checks and model quirks stay in the command layer; a backend only reads and writes.
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn capabilities(&self) -> Capabilities;
+    /// Nodes other power managers also write, watched for changes the daemon didn't make.
+    fn watched(&self) -> Vec<PathBuf>;
+    /// Nodes the backend writes, checked once the daemon has dropped its privileges.
+    fn nodes(&self) -> Vec<PathBuf>;
+
+    fn profile(&self) -> Result<String, String>;
+    /// The interface the profile is switched through, e.g. `platform_profile`.
+    fn profile_interface(&self) -> Option<&'static str>;
+    fn profile_choices(&self) -> Vec<PlatformProfile>;
+    fn set_profile(&self, profile: PlatformProfile) -> Result<(), String>;
+
+    fn battery_threshold(&self) -> Result<u32, String>;
+    fn set_battery_threshold(&self, value: u32) -> Result<(), String>;
+    fn battery(&self) -> Battery;
+    /// Whether mains power is connected, or `None` if there is no mains supply to ask.
+    fn ac_online(&self) -> Option<bool>;
+
+    fn fans(&self) -> Vec<FanReading>;
+
+    fn keyboard_brightness(&self) -> Result<u32, String>;
+    fn max_keyboard_brightness(&self) -> Result<u32, String>;
+    fn set_keyboard_brightness(&self, value: u32) -> Result<(), String>;
+}
+
+/// Where each feature lives on a backend, with details, or `None` if it is missing.
+pub struct Capabilities {
+    pub profile: Option<String>,
+    pub battery_threshold: Option<String>,
+    pub fans: Option<String>,
+    pub keyboard_backlight: Option<String>,
+}
+
+/// What a backend can read about the battery besides its end threshold; unreadable values
+/// are `None`.
+#[derive(Default)]
+pub struct Battery {
+    /// Charge in percent.
+    pub capacity: Option<u32>,
+    /// The power_supply status, e.g. `Charging` or `Not charging`.
+    pub status: Option<String>,
+    /// Charge or discharge rate in watts.
+    pub power: Option<f64>,
+    pub start_threshold: Option<u32>,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// A backend this thread uses instead of sysfs, so tests can run the command layer
+    /// against a double.
+    static TEST_BACKEND: std::cell::Cell<Option<&'static dyn Backend>> =
+        const { std::cell::Cell::new(None) };
+}
+
+/// Routes this thread's hardware access through `backend`.
+#[cfg(test)]
+pub fn set_for_thread(backend: Box<dyn Backend>) {
+    TEST_BACKEND.with(|b| b.set(Some(Box::leak(backend))));
+}
+
+/// The backend of threads a test can't reach, such as the D-Bus executor's.
+#[cfg(test)]
+static SHARED_TEST_BACKEND: OnceLock<&'static dyn Backend> = OnceLock::new();
+
+/// Routes the hardware access of threads without their own backend through `backend`. Only
+/// the first call takes effect.
+#[cfg(test)]
+pub fn set_shared(backend: Box<dyn Backend>) {
+    SHARED_TEST_BACKEND.get_or_init(|| Box::leak(backend));
+}
+
+pub fn get() -> &'static dyn Backend {
+    #[cfg(test)]
+    if let Some(backend) = TEST_BACKEND
+        .with(|b| b.get())
+        .or_else(|| SHARED_TEST_BACKEND.get().copied())
+    {
+        return backend;
+    }
+    &Sysfs
+}
+
+fn choices_list(choices: &[PlatformProfile]) -> String {
+    choices
+        .iter()
+        .map(|p| p.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+/// The battery named by a quirk, else the first one with a charge threshold.
+fn battery_threshold_path() -> String {
+    let path = |name: &str| {
+        sysfs::path(&format!(
+            "{}/{}/charge_control_end_threshold",
+            POWER_SUPPLY_DIR, name
+        ))
+        .display()
+        .to_string()
+    };
+    if let Some(name) = &quirks::get().battery {
+        return path(name);
+    }
+    ["BAT0", "BAT1", "BATT", "BATC"]
+        .into_iter()
+        .map(path)
+        .find(|p| Path::new(p).exists())
+        .unwrap_or_else(|| path("BAT0"))
+}
+
+/// The kernel's asus-wmi, platform_profile, power_supply, hwmon and LED class nodes.
+pub struct Sysfs;
+
+impl Sysfs {
+    fn profile_backend() -> Result<ProfileBackend, String> {
+        ProfileBackend::detect().ok_or_else(|| "no platform profile interface found".into())
+    }
+}
+
+impl Backend for Sysfs {
+    fn name(&self) -> &'static str {
+        "sysfs"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let battery = battery_threshold_path();
+        let fan_dirs = hwmon_devices()
+            .into_iter()
+            .filter(|(dir, _)| !channels(dir, "fan").is_empty())
+            .map(|(dir, _)| dir.display().to_string())
+            .collect::<Vec<_>>();
+
+        Capabilities {
+            profile: ProfileBackend::detect().map(|b| {
+                format!(
+                    "{} backend={} choices={}",
+                    b.path().display(),
+                    b.name(),
+                    choices_list(&b.choices())
+                )
+            }),
+            battery_threshold: Path::new(&battery).exists().then_some(battery),
+            fans: (!fan_dirs.is_empty()).then(|| fan_dirs.join(",")),
+            keyboard_backlight: Some(sysfs::path(KBD_BACKLIGHT_DIR))
+                .filter(|p| p.exists())
+                .map(|p| p.display().to_string()),
+        }
+    }
+
+    fn watched(&self) -> Vec<PathBuf> {
+        let battery = PathBuf::from(battery_threshold_path());
+        ProfileBackend::detect()
+            .map(|b| b.path())
+            .into_iter()
+            .chain(battery.exists().then_some(battery))
+            .collect()
+    }
+
+    fn nodes(&self) -> Vec<PathBuf> {
+        let brightness = sysfs::path(KBD_BACKLIGHT_DIR).join("brightness");
+        let mut nodes = self.watched();
+        nodes.extend(brightness.exists().then_some(brightness));
+        nodes
+    }
+
+    fn profile(&self) -> Result<String, String> {
+        let backend = Self::profile_backend()?;
+        let path = backend.path();
+        let s = std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Ok(backend.decode(s.trim()))
+    }
+
+    fn profile_interface(&self) -> Option<&'static str> {
+        ProfileBackend::detect().map(|b| b.name())
+    }
+
+    fn profile_choices(&self) -> Vec<PlatformProfile> {
+        ProfileBackend::detect().map_or_else(Vec::new, |b| b.choices())
+    }
+
+    fn set_profile(&self, profile: PlatformProfile) -> Result<(), String> {
+        let backend = Self::profile_backend()?;
+        sysfs::write(backend.path(), backend.encode(profile))
+    }
+
+    fn battery_threshold(&self) -> Result<u32, String> {
+        let path = battery_threshold_path();
+        let s = std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {}", path, e))?;
+        s.trim()
+            .parse()
+            .map_err(|_| format!("invalid value in {}: {}", path, s.trim()))
+    }
+
+    fn set_battery_threshold(&self, value: u32) -> Result<(), String> {
+        sysfs::write(battery_threshold_path(), &value.to_string())
+    }
+
+    fn battery(&self) -> Battery {
+        let path = battery_threshold_path();
+        let Some(dir) = Path::new(&path).parent() else {
+            return Battery::default();
+        };
+        let read = |node: &str| read_trimmed(&dir.join(node));
+        Battery {
+            capacity: read("capacity").and_then(|v| v.parse().ok()),
+            status: read("status"),
+            power: read("power_now")
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|uw| uw / 1e6),
+            start_threshold: read("charge_control_start_threshold").and_then(|v| v.parse().ok()),
+        }
+    }
+
+    fn ac_online(&self) -> Option<bool> {
+        let entries = std::fs::read_dir(sysfs::path(POWER_SUPPLY_DIR)).ok()?;
+        let mut found = None;
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if read_trimmed(&dir.join("type")).as_deref() != Some("Mains") {
+                continue;
+            }
+            let online = read_trimmed(&dir.join("online")).is_some_and(|v| v == "1");
+            found = Some(found.unwrap_or(false) || online);
+        }
+        found
+    }
+
+    fn fans(&self) -> Vec<FanReading> {
+        sensors::fans()
+    }
+
+    fn keyboard_brightness(&self) -> Result<u32, String> {
+        let path = sysfs::path(KBD_BACKLIGHT_DIR).join("brightness");
+        read_trimmed(&path)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("failed to read {}", path.display()))
+    }
+
+    fn max_keyboard_brightness(&self) -> Result<u32, String> {
+        let path = sysfs::path(KBD_BACKLIGHT_DIR).join("max_brightness");
+        read_trimmed(&path)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| format!("failed to read {}", path.display()))
+    }
+
+    fn set_keyboard_brightness(&self, value: u32) -> Result<(), String> {
+        sysfs::write(
+            sysfs::path(KBD_BACKLIGHT_DIR).join("brightness"),
+            &value.to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+struct MemoryState {
+    profile: PlatformProfile,
+    battery_threshold: u32,
+    keyboard_brightness: u32,
+}
+
+/// A laptop held entirely in memory, for exercising the command layer without touching any
+/// hardware. Fans report a fixed speed per profile.
+#[cfg(test)]
+pub struct Memory {
+    state: Mutex<MemoryState>,
+}
+
+#[cfg(test)]
+impl Memory {
+    const MAX_KEYBOARD_BRIGHTNESS: u32 = 3;
+
+    pub fn new() -> Memory {
+        Memory {
+            state: Mutex::new(MemoryState {
+                profile: PlatformProfile::Balanced,
+                battery_threshold: 100,
+                keyboard_brightness: 0,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Backend for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            profile: Some(format!(
+                "memory backend=memory choices={}",
+                choices_list(&self.profile_choices())
+            )),
+            battery_threshold: Some("memory".into()),
+            fans: Some("memory".into()),
+            keyboard_backlight: Some("memory".into()),
+        }
+    }
+
+    fn watched(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn nodes(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn profile(&self) -> Result<String, String> {
+        Ok(self.state.lock().unwrap().profile.as_str().into())
+    }
+
+    fn profile_interface(&self) -> Option<&'static str> {
+        Some("memory")
+    }
+
+    fn profile_choices(&self) -> Vec<PlatformProfile> {
+        vec![
+            PlatformProfile::Quiet,
+            PlatformProfile::Balanced,
+            PlatformProfile::Performance,
+        ]
+    }
+
+    fn set_profile(&self, profile: PlatformProfile) -> Result<(), String> {
+        if !sysfs::record("memory:profile", profile.as_str()) {
+            self.state.lock().unwrap().profile = profile;
+        }
+        Ok(())
+    }
+
+    fn battery_threshold(&self) -> Result<u32, String> {
+        Ok(self.state.lock().unwrap().battery_threshold)
+    }
+
+    fn set_battery_threshold(&self, value: u32) -> Result<(), String> {
+        if !sysfs::record("memory:battery-threshold", &value.to_string()) {
+            self.state.lock().unwrap().battery_threshold = value;
+        }
+        Ok(())
+    }
+
+    /// Plugged in and held at the threshold.
+    fn battery(&self) -> Battery {
+        Battery {
+            capacity: Some(self.state.lock().unwrap().battery_threshold),
+            status: Some("Not charging".into()),
+            power: Some(0.0),
+            start_threshold: None,
+        }
+    }
+
+    fn ac_online(&self) -> Option<bool> {
+        Some(true)
+    }
+
+    fn fans(&self) -> Vec<FanReading> {
+        let (cpu, gpu) = match self.state.lock().unwrap().profile {
+            PlatformProfile::Quiet => (1800, 1500),
+            PlatformProfile::Balanced => (2800, 2500),
+            PlatformProfile::Performance => (4600, 4200),
+        };
+        vec![
+            FanReading {
+                label: "cpu_fan".into(),
+                rpm: cpu,
+            },
+            FanReading {
+                label: "gpu_fan".into(),
+                rpm: gpu,
+            },
+        ]
+    }
+
+    fn keyboard_brightness(&self) -> Result<u32, String> {
+        Ok(self.state.lock().unwrap().keyboard_brightness)
+    }
+
+    fn max_keyboard_brightness(&self) -> Result<u32, String> {
+        Ok(Self::MAX_KEYBOARD_BRIGHTNESS)
+    }
+
+    fn set_keyboard_brightness(&self, value: u32) -> Result<(), String> {
+        if !sysfs::record("memory:keyboard-backlight", &value.to_string()) {
+            self.state.lock().unwrap().keyboard_brightness = value;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use crate::backend;
+use crate::fan_curve;
+use crate::firmware_attrs::{self, ATTRIBUTES_DIR};
+use crate::sysfs;
+
+/// The hardware backend in use, then one `feature: <location> [details]` line per feature,
+/// or `feature: unavailable`.
+pub fn get_capabilities() -> String {
+    let backend = backend::get();
+    let mut lines = vec![format!("backend: {}", backend.name())];
+    let mut report = |feature: &str, found: Option<String>| {
+        lines.push(format!(
+            "{}: {}",
+            feature,
+            found.unwrap_or_else(|| "unavailable".into())
+        ));
+    };
+
+    let caps = backend.capabilities();
+    report("battery-threshold", caps.battery_threshold);
+    report("profile", caps.profile);
+    report("fans", caps.fans);
+    report("keyboard-backlight", caps.keyboard_backlight);
+
+    report(
+        "fan-curves",
+        fan_curve::available()
+            .filter(|(_, fans)| !fans.is_empty())
+            .map(|(dir, fans)| {
+                let fans = fans.iter().map(|f| f.as_str()).collect::<Vec<_>>();
+                format!("{} fans={}", dir.display(), fans.join(","))
+            }),
+    );
+
+    let attrs = firmware_attrs::attributes();
+    report(
+        "firmware-attributes",
+        (!attrs.is_empty()).then(|| {
+            format!(
+                "{} count={}",
+                sysfs::path(ATTRIBUTES_DIR).display(),
+                attrs.len()
+            )
+        }),
+    );
+
+    lines.join("\n")
+}
@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde::de::{self, Deserializer};
+
+use crate::cpu_tuning;
+use crate::metrics;
+use crate::power_knobs;
+use crate::presets;
+use crate::profile::PlatformProfile;
+
+pub const CONFIG_PATH: &str = "/etc/asus-control/daemon.toml";
+pub const STATE_DIR: &str = "/var/lib/asus-control";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub app_profiles: AppProfilesConfig,
+    pub sampler: SamplerConfig,
+    pub metrics: MetricsConfig,
+    pub dbus: DbusConfig,
+    /// Settings applied when the daemon starts, in the same form as a preset.
+    pub defaults: Option<Preset>,
+    /// Extra settings applied together with each platform profile.
+    pub profile: HashMap<PlatformProfile, ProfileSettings>,
+    /// Named bundles of `set` targets and values, applied with `apply preset`.
+    pub preset: BTreeMap<String, Preset>,
+}
+
+/// A table of `set` targets and values, checked while parsing so errors point at it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "toml::Value")]
+pub struct Preset(pub toml::Value);
+
+impl TryFrom<toml::Value> for Preset {
+    type Error = String;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        presets::entries(&value)?;
+        Ok(Preset(value))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AppProfilesConfig {
+    /// Seconds between two scans of /proc.
+    pub interval: u64,
+    pub rule: Vec<AppRule>,
+}
+
+impl Default for AppProfilesConfig {
+    fn default() -> Self {
+        AppProfilesConfig {
+            interval: 2,
+            rule: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SamplerConfig {
+    /// Seconds between two sensor readings.
+    pub interval: u64,
+    /// Number of samples kept for `get sensor-history`.
+    pub history: usize,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            interval: 2,
+            history: 150,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MetricsConfig {
+    /// A loopback `address:port` or a unix socket path; metrics are off when unset.
+    pub listen: Option<metrics::Listen>,
+}
+
+/// The bus names are owned as the daemon's `--user`, which the bus policy must allow; see
+/// `asus-control-daemon.conf`. Only root and members of asus-control may change settings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DbusConfig {
+    /// A bus address such as `unix:path=/tmp/test-bus`; the system bus when unset.
+    pub address: Option<String>,
+    /// Serve power-profiles-daemon's interface so desktop profile toggles drive this daemon.
+    pub power_profiles: bool,
+    /// Serve a subset of asusd's interfaces for asusctl frontends.
+    pub asusd: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct AppRule {
+    /// Process names or executable paths that trigger this rule. Other users' executable
+    /// paths can't be read inside the sandbox, so only their process names match there.
+    pub executables: Vec<String>,
+    pub profile: PlatformProfile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ProfileSettings {
+    #[serde(deserialize_with = "preference")]
+    pub energy_performance_preference: Option<String>,
+    #[serde(deserialize_with = "governor")]
+    pub scaling_governor: Option<String>,
+    /// CPU turbo boost, through cpufreq/boost or intel_pstate/no_turbo.
+    pub boost: Option<bool>,
+    #[serde(deserialize_with = "aspm_policy")]
+    pub pcie_aspm_policy: Option<String>,
+    /// Default suspend mode, such as s2idle or deep.
+    #[serde(deserialize_with = "mem_sleep")]
+    pub mem_sleep: Option<String>,
+    pub nmi_watchdog: Option<bool>,
+}
+
+/// Reads a string that must be one of `allowed`.
+fn one_of<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    allowed: &[&str],
+) -> Result<Option<String>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    if !allowed.contains(&value.as_str()) {
+        return Err(de::Error::custom(format!(
+            "{} is not one of {}",
+            value,
+            allowed.join(", ")
+        )));
+    }
+    Ok(Some(value))
+}
+
+fn preference<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    one_of(d, cpu_tuning::PREFERENCES)
+}
+
+fn governor<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    one_of(d, cpu_tuning::GOVERNORS)
+}
+
+fn aspm_policy<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    one_of(d, power_knobs::ASPM_POLICIES)
+}
+
+fn mem_sleep<'de, D: Deserializer<'de>>(d: D) -> Result<Option<String>, D::Error> {
+    one_of(d, power_knobs::MEM_SLEEP_MODES)
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+static STATE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn init(path: &Path) -> Result<(), String> {
+    let _ = CONFIG.set(load(path)?);
+    let _ = PATH.set(path.to_path_buf());
+    Ok(())
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The config file in use, which `--config` may have changed.
+pub fn path() -> &'static Path {
+    PATH.get_or_init(|| PathBuf::from(CONFIG_PATH))
+}
+
+/// Keeps the daemon's state files in `dir` instead of [`STATE_DIR`].
+pub fn set_state_dir(dir: PathBuf) {
+    let _ = STATE.set(dir);
+}
+
+pub fn state_dir() -> &'static Path {
+    STATE.get_or_init(|| PathBuf::from(STATE_DIR))
+}
+
+/// Reads and validates `path`; a missing file means the defaults. Errors name the file and,
+/// for problems in its contents, the line and field.
+pub fn load(path: &Path) -> Result<Config, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+    };
+    toml::from_str(&text).map_err(|e| format!("invalid {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_line_and_field_of_config_errors() {
+        let dir = std::env::temp_dir().join(format!("asus-control-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("daemon.toml");
+
+        assert!(load(&path).is_ok_and(|c| c.profile.is_empty()));
+        assert!(load(&dir).unwrap_err().starts_with("failed to read"));
+
+        let cases = [
+            (
+                "[profile.quiet]\nmem-sleep = \"hibernate\"\n",
+                2,
+                "hibernate is not one of s2idle, shallow, deep",
+            ),
+            (
+                "[sampler]\nbogus = 1\n",
+                2,
+                "unknown field `bogus`, expected `interval` or `history`",
+            ),
+            ("[app-profiles\n", 1, "unclosed table, expected `]`"),
+            (
+                "[[app-profiles.rule]]\nexecutables = [\"x\"]\nprofile = \"turbo\"\n",
+                3,
+                "unknown variant `turbo`, expected one of `quiet`, `balanced`, `performance`",
+            ),
+            (
+                "[preset.a]\nbattery-threshold = \"x\"\n",
+                1,
+                "battery-threshold: threshold out of range (0-100): x",
+            ),
+        ];
+        for (contents, line, message) in cases {
+            std::fs::write(&path, contents).unwrap();
+            let error = load(&path).unwrap_err();
+            assert!(
+                error.starts_with(&format!(
+                    "invalid {}: TOML parse error at line {},",
+                    path.display(),
+                    line
+                )),
+                "{}",
+                error
+            );
+            assert!(error.contains(message), "{}", error);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+use zbus::blocking::{Connection, fdo::DBusProxy};
+use zbus::names::BusName;
+
+use crate::backend;
+use crate::sensors::read_trimmed;
+
+/// How often the watched nodes are compared against what was last seen in them.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the other power managers are looked for again.
+const DETECT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Process names, as truncated to 15 characters in `/proc/<pid>/comm`.
+const PROCESSES: &[(&str, &str)] = &[
+    ("power-profiles-", "power-profiles-daemon"),
+    ("asusd", "asusd"),
+];
+
+const BUS_NAMES: &[(&str, &str)] = &[
+    ("net.hadess.PowerProfiles", "power-profiles-daemon"),
+    (
+        "org.freedesktop.UPower.PowerProfiles",
+        "power-profiles-daemon",
+    ),
+    ("xyz.ljones.Asusd", "asusd"),
+];
+
+/// TLP settings that write the platform profile or the charge thresholds.
+const TLP_KEYS: &[&str] = &[
+    "PLATFORM_PROFILE_ON_AC",
+    "PLATFORM_PROFILE_ON_BAT",
+    "START_CHARGE_THRESH_BAT0",
+    "STOP_CHARGE_THRESH_BAT0",
+    "START_CHARGE_THRESH_BAT1",
+    "STOP_CHARGE_THRESH_BAT1",
+];
+
+/// Another power manager writing the same nodes, and what gave it away.
+#[derive(Clone, PartialEq)]
+pub struct Conflict {
+    pub name: &'static str,
+    pub evidence: Vec<String>,
+}
+
+static DETECTED: Mutex<Vec<Conflict>> = Mutex::new(Vec::new());
+/// The last value seen in each watched node, including the daemon's own writes.
+static SEEN: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+fn processes() -> Vec<(&'static str, String)> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for entry in entries.flatten() {
+        let Some(comm) = read_trimmed(&entry.path().join("comm")) else {
+            continue;
+        };
+        for (process, name) in PROCESSES {
+            if comm == *process && !found.iter().any(|(n, _)| n == name) {
+                found.push((*name, format!("process {}", comm)));
+            }
+        }
+    }
+    found
+}
+
+fn bus_names() -> Vec<(&'static str, String)> {
+    let owned = |conn: &Connection| -> zbus::Result<Vec<(&'static str, String)>> {
+        let proxy = DBusProxy::new(conn)?;
+        let mut found = Vec::new();
+        for (bus_name, name) in BUS_NAMES {
+            let bus_name = BusName::try_from(*bus_name)?;
+            // The daemon may own these names itself when serving the compatible interfaces.
+            if proxy.name_has_owner(bus_name.clone())?
+                && proxy.get_connection_unix_process_id(bus_name.clone())? != std::process::id()
+            {
+                found.push((*name, format!("D-Bus name {}", bus_name)));
+            }
+        }
+        Ok(found)
+    };
+    match Connection::system().and_then(|conn| owned(&conn)) {
+        Ok(found) => found,
+        Err(e) => {
+            debug!("Can't look for power managers on the system bus: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// TLP's effective settings from `/etc/tlp.conf` and `/etc/tlp.d`, later files winning.
+fn tlp() -> Vec<(&'static str, String)> {
+    let mut files = vec![PathBuf::from("/etc/tlp.conf")];
+    if let Ok(entries) = fs::read_dir("/etc/tlp.d") {
+        let mut dropins: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "conf"))
+            .collect();
+        dropins.sort();
+        files.extend(dropins);
+    }
+
+    let mut settings = BTreeMap::new();
+    for file in files {
+        let Ok(contents) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches('"');
+                settings.insert(key.trim().to_string(), value.to_string());
+            }
+        }
+    }
+
+    if settings.get("TLP_ENABLE").is_some_and(|v| v == "0") {
+        return Vec::new();
+    }
+    TLP_KEYS
+        .iter()
+        .filter_map(|key| {
+            let value = settings.get(*key).filter(|v| !v.is_empty())?;
+            Some(("tlp", format!("{}={}", key, value)))
+        })
+        .collect()
+}
+
+/// Looks for every known power manager, one entry per manager.
+fn detect() -> Vec<Conflict> {
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    for (name, evidence) in processes().into_iter().chain(bus_names()).chain(tlp()) {
+        match conflicts.iter_mut().find(|c| c.name == name) {
+            Some(c) => c.evidence.push(evidence),
+            None => conflicts.push(Conflict {
+                name,
+                evidence: vec![evidence],
+            }),
+        }
+    }
+    conflicts
+}
+
+/// Logs managers that appeared or went away since the last detection.
+fn redetect() {
+    let found = detect();
+    let mut detected = DETECTED.lock().unwrap();
+    for c in found.iter().filter(|c| !detected.contains(c)) {
+        warn!(
+            "{} also manages the platform profile or battery ({}); settings may be overridden",
+            c.name,
+            c.evidence.join(", ")
+        );
+    }
+    for c in detected
+        .iter()
+        .filter(|c| !found.iter().any(|f| f.name == c.name))
+    {
+        info!("{} is no longer detected", c.name);
+    }
+    *detected = found;
+}
+
+/// Records the value in each watched node, warning when one changed since it was last seen.
+fn check() {
+    let suspects = DETECTED
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>();
+    let mut seen = SEEN.lock().unwrap();
+    for path in backend::get().watched() {
+        let Some(value) = read_trimmed(&path) else {
+            continue;
+        };
+        if let Some(old) = seen.get(&path)
+            && *old != value
+        {
+            let culprit = match suspects.is_empty() {
+                true => String::new(),
+                false => format!(", possibly by {}", suspects.join(" or ")),
+            };
+            warn!(
+                sysfs_path = %path.display(),
+                "Changed from {} to {} outside the daemon{}",
+                old,
+                value,
+                culprit
+            );
+        }
+        seen.insert(path, value);
+    }
+}
+
+/// Runs the daemon's own write to `path`, so the watcher doesn't mistake it for someone else's.
+pub fn own_write<T>(path: &Path, write: impl FnOnce() -> T) -> T {
+    let mut seen = SEEN.lock().unwrap();
+    let result = write();
+    if seen.contains_key(path)
+        && let Some(value) = read_trimmed(path)
+    {
+        seen.insert(path.to_path_buf(), value);
+    }
+    result
+}
+
+pub fn spawn() {
+    thread::spawn(|| {
+        let mut last_detect = Instant::now();
+        redetect();
+        loop {
+            check();
+            if last_detect.elapsed() >= DETECT_INTERVAL {
+                last_detect = Instant::now();
+                redetect();
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+    });
+}
+
+/// `name (evidence, ...)` for each manager found, or `none`.
+pub fn summary() -> String {
+    let detected = DETECTED.lock().unwrap();
+    if detected.is_empty() {
+        return "none".into();
+    }
+    detected
+        .iter()
+        .map(|c| format!("{} ({})", c.name, c.evidence.join(", ")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::{self, ProfileSettings};
+use crate::profile::PlatformProfile;
+use crate::sensors::read_trimmed;
+use crate::sysfs;
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+const CPUFREQ_BOOST: &str = "/sys/devices/system/cpu/cpufreq/boost";
+const INTEL_NO_TURBO: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+
+/// Governors the kernel can offer; which ones a CPU lists depends on its driver.
+pub const GOVERNORS: &[&str] = &[
+    "performance",
+    "powersave",
+    "schedutil",
+    "ondemand",
+    "conservative",
+    "userspace",
+];
+/// Preferences intel_pstate and amd-pstate accept.
+pub const PREFERENCES: &[&str] = &[
+    "default",
+    "performance",
+    "balance_performance",
+    "balance_power",
+    "power",
+];
+
+/// The cpufreq directory of every CPU that has one.
+fn policies() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(sysfs::path(CPU_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .and_then(|n| n.strip_prefix("cpu"))
+                .is_some_and(|n| n.parse::<u32>().is_ok())
+        })
+        .map(|e| e.path().join("cpufreq"))
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Writes `value` to `node` in every cpufreq directory, if `available` lists it.
+fn write_all(node: &str, available: &str, value: &str) -> Result<(), String> {
+    for dir in policies() {
+        if let Some(choices) = read_trimmed(&dir.join(available))
+            && !choices.split_whitespace().any(|c| c == value)
+        {
+            return Err(format!("{} is not one of {} ({})", value, node, choices));
+        }
+        sysfs::write_over_baseline(dir.join(node), value)?;
+    }
+    Ok(())
+}
+
+/// Puts `node` in every cpufreq directory back to what it was before the daemon changed it.
+fn restore_all(node: &str) -> Result<(), String> {
+    policies()
+        .into_iter()
+        .try_for_each(|dir| sysfs::restore_baseline(dir.join(node)))
+}
+
+/// The governor and EPP of every CPU, and whichever boost switch exists.
+pub fn nodes() -> Vec<PathBuf> {
+    policies()
+        .iter()
+        .flat_map(|dir| {
+            ["scaling_governor", "energy_performance_preference"].map(|node| dir.join(node))
+        })
+        .chain([sysfs::path(CPUFREQ_BOOST), sysfs::path(INTEL_NO_TURBO)])
+        .filter(|path| path.exists())
+        .collect()
+}
+
+fn set_boost(on: bool) -> Result<(), String> {
+    let (boost, no_turbo) = (sysfs::path(CPUFREQ_BOOST), sysfs::path(INTEL_NO_TURBO));
+    if boost.exists() {
+        sysfs::write_over_baseline(boost, if on { "1" } else { "0" })
+    } else if no_turbo.exists() {
+        sysfs::write_over_baseline(no_turbo, if on { "0" } else { "1" })
+    } else {
+        Err("no CPU boost control found".into())
+    }
+}
+
+/// Applies the cpufreq settings configured for `profile`. Settings it leaves out go back to
+/// what they were before the daemon first changed them, so they don't carry over from the
+/// previous profile.
+pub fn apply(profile: PlatformProfile) -> Result<(), String> {
+    let default = ProfileSettings::default();
+    let settings = config::get().profile.get(&profile).unwrap_or(&default);
+    let mut errors = Vec::new();
+
+    // The governor goes first: intel_pstate only accepts EPP changes under powersave.
+    let governor = match &settings.scaling_governor {
+        Some(governor) => write_all("scaling_governor", "scaling_available_governors", governor),
+        None => restore_all("scaling_governor"),
+    };
+    if let Err(e) = governor {
+        errors.push(format!("failed to set scaling governor: {}", e));
+    }
+    let epp = match &settings.energy_performance_preference {
+        Some(epp) => write_all(
+            "energy_performance_preference",
+            "energy_performance_available_preferences",
+            epp,
+        ),
+        None => restore_all("energy_performance_preference"),
+    };
+    if let Err(e) = epp {
+        errors.push(format!(
+            "failed to set energy performance preference: {}",
+            e
+        ));
+    }
+    let boost = match settings.boost {
+        Some(boost) => set_boost(boost),
+        None => sysfs::restore_baseline(sysfs::path(CPUFREQ_BOOST))
+            .and_then(|_| sysfs::restore_baseline(sysfs::path(INTEL_NO_TURBO))),
+    };
+    if let Err(e) = boost {
+        errors.push(format!("failed to set CPU boost: {}", e));
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("; ")),
+    }
+}
+
+/// Distinct values of `node` across all CPUs, comma separated.
+fn read_all(node: &str) -> String {
+    let mut values: Vec<String> = policies()
+        .iter()
+        .filter_map(|dir| read_trimmed(&dir.join(node)))
+        .collect();
+    values.sort();
+    values.dedup();
+    if values.is_empty() {
+        "unavailable".into()
+    } else {
+        values.join(",")
+    }
+}
+
+fn read_boost() -> String {
+    if let Some(v) = read_trimmed(&sysfs::path(CPUFREQ_BOOST)) {
+        format!("{} (cpufreq/boost)", if v == "1" { "on" } else { "off" })
+    } else if let Some(v) = read_trimmed(&sysfs::path(INTEL_NO_TURBO)) {
+        format!(
+            "{} (intel_pstate/no_turbo)",
+            if v == "0" { "on" } else { "off" }
+        )
+    } else {
+        "unavailable".into()
+    }
+}
+
+pub fn get_cpu_tuning() -> String {
+    [
+        format!(
+            "energy-performance-preference: {}",
+            read_all("energy_performance_preference")
+        ),
+        format!("scaling-governor: {}", read_all("scaling_governor")),
+        format!("boost: {}", read_boost()),
+    ]
+    .join("\n")
+}
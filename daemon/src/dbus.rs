@@ -0,0 +1,140 @@
+use std::ffi::CString;
+use std::fs;
+use std::sync::OnceLock;
+
+use tracing::{info, warn};
+use zbus::blocking::{Connection, connection};
+use zbus::fdo::{self, ConnectionCredentials, DBusProxy, RequestNameFlags};
+use zbus::message::Header;
+
+use crate::asusd;
+use crate::config::DbusConfig;
+use crate::power_profiles;
+
+/// The group allowed on the daemon's socket, whose members may also change settings over D-Bus.
+const GROUP: &str = "asus-control";
+
+static GROUP_ID: OnceLock<Option<u32>> = OnceLock::new();
+
+/// Looks up [`GROUP`] while the group database is still readable. Must run before the sandbox
+/// and before any other thread is started.
+pub fn init() {
+    GROUP_ID.get_or_init(|| {
+        let name = CString::new(GROUP).ok()?;
+        // SAFETY: the process is still single-threaded, so nothing else can reuse the static
+        // entry getgrnam returns before its gid is copied out.
+        let group = unsafe { libc::getgrnam(name.as_ptr()) };
+        // SAFETY: a non-null entry points to a valid group.
+        (!group.is_null()).then(|| unsafe { (*group).gr_gid })
+    });
+}
+
+/// Lets the sender of `header` change settings only if it runs as root, as the daemon's own
+/// user or in [`GROUP`], the same callers the socket admits.
+pub async fn authorize(conn: &zbus::Connection, header: Option<&Header<'_>>) -> fdo::Result<()> {
+    let denied = || {
+        fdo::Error::AccessDenied(format!(
+            "only root and members of {} may change settings",
+            GROUP
+        ))
+    };
+    let (uid, groups) = match conn.is_bus() {
+        true => {
+            let sender = header.and_then(|h| h.sender()).ok_or_else(denied)?;
+            let credentials = DBusProxy::new(conn)
+                .await?
+                .get_connection_credentials(sender.clone().into())
+                .await?;
+            (credentials.unix_user_id(), groups(&credentials))
+        }
+        // A peer-to-peer caller has no bus to vouch for it, but the socket knows its peer.
+        false => {
+            let credentials = conn
+                .peer_creds()
+                .await
+                .map_err(|e| fdo::Error::IOError(e.to_string()))?;
+            (credentials.unix_user_id(), groups(credentials))
+        }
+    };
+    let in_group = GROUP_ID
+        .get()
+        .copied()
+        .flatten()
+        .is_some_and(|gid| groups.contains(&gid));
+    // SAFETY: geteuid has no preconditions.
+    let own = uid == Some(unsafe { libc::geteuid() });
+    match uid == Some(0) || own || in_group {
+        true => Ok(()),
+        false => Err(denied()),
+    }
+}
+
+/// The groups in `credentials`, or those of its process if the caller's bus left them out.
+fn groups(credentials: &ConnectionCredentials) -> Vec<u32> {
+    match credentials.unix_group_ids() {
+        Some(ids) => ids.clone(),
+        None => credentials
+            .process_id()
+            .map(process_groups)
+            .unwrap_or_default(),
+    }
+}
+
+/// The supplementary groups of `pid`, for buses that don't report them with the credentials.
+fn process_groups(pid: u32) -> Vec<u32> {
+    fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|l| l.strip_prefix("Groups:"))
+                .map(|ids| {
+                    ids.split_whitespace()
+                        .filter_map(|id| id.parse().ok())
+                        .collect()
+                })
+        })
+        .unwrap_or_default()
+}
+
+fn connect(config: &DbusConfig) -> zbus::Result<Connection> {
+    match &config.address {
+        Some(address) => connection::Builder::address(address.as_str())?.build(),
+        None => connection::Builder::system()?.build(),
+    }
+}
+
+/// Serves the enabled compatibility interfaces, then claims their bus names one by one so a
+/// name another service already owns only disables that name.
+pub fn spawn(config: &'static DbusConfig) {
+    if !config.power_profiles && !config.asusd {
+        return;
+    }
+    let conn = match connect(config) {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!("Failed to connect to D-Bus: {}", e);
+            return;
+        }
+    };
+
+    let mut names = Vec::new();
+    if config.power_profiles {
+        match power_profiles::serve(&conn) {
+            Ok(()) => names.extend(power_profiles::BUS_NAMES),
+            Err(e) => warn!("Failed to serve the power-profiles-daemon interface: {}", e),
+        }
+    }
+    if config.asusd {
+        match asusd::serve(&conn) {
+            Ok(()) => names.push(asusd::BUS_NAME),
+            Err(e) => warn!("Failed to serve the asusd interfaces: {}", e),
+        }
+    }
+    for name in names {
+        match conn.request_name_with_flags(name, RequestNameFlags::DoNotQueue.into()) {
+            Ok(_) => info!("Serving {} on D-Bus", name),
+            Err(e) => warn!("Failed to own {} on D-Bus: {}", name, e),
+        }
+    }
+}
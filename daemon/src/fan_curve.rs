@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::config;
+use crate::profile::{PlatformProfile, get_fan_profile};
+use crate::sensors::{hwmon_devices, read_trimmed};
+use crate::sysfs;
+use crate::transaction;
+
+const HWMON_NAME: &str = "asus_custom_fan_curve";
+const POINTS: usize = 8;
+/// asus-wmi rejects curve temperatures above this.
+const TEMP_MAX: u8 = 100;
+
+/// Stored curves, keyed by profile and then by fan.
+type Curves = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fan {
+    Cpu,
+    Gpu,
+    Mid,
+}
+
+impl Fan {
+    pub fn parse(s: &str) -> Option<Fan> {
+        match s {
+            "cpu" => Some(Fan::Cpu),
+            "gpu" => Some(Fan::Gpu),
+            "mid" => Some(Fan::Mid),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fan::Cpu => "cpu",
+            Fan::Gpu => "gpu",
+            Fan::Mid => "mid",
+        }
+    }
+
+    fn pwm(&self) -> u32 {
+        match self {
+            Fan::Cpu => 1,
+            Fan::Gpu => 2,
+            Fan::Mid => 3,
+        }
+    }
+}
+
+/// Eight (temperature in °C, duty in percent) points.
+pub struct Curve([(u8, u8); POINTS]);
+
+impl Curve {
+    /// Parses and validates `temp:percent,temp:percent,...`.
+    pub fn parse(s: &str) -> Result<Curve, String> {
+        let points = s
+            .split(',')
+            .map(|p| {
+                let (t, d) = p
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid curve point: {}", p))?;
+                let t = t
+                    .trim()
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid curve temperature: {}", t))?;
+                let d = d
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid curve duty: {}", d))?;
+                Ok((t, d))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let points: [(u8, u8); POINTS] = points
+            .try_into()
+            .map_err(|_| format!("fan curve needs exactly {} points", POINTS))?;
+
+        for (t, d) in points {
+            if t > TEMP_MAX {
+                return Err(format!("curve temperature {} above {}", t, TEMP_MAX));
+            }
+            if d > 100 {
+                return Err(format!("curve duty {} above 100%", d));
+            }
+        }
+        for w in points.windows(2) {
+            if w[1].0 < w[0].0 || w[1].1 < w[0].1 {
+                return Err("fan curve must be monotonic".into());
+            }
+        }
+
+        Ok(Curve(points))
+    }
+}
+
+impl std::fmt::Display for Curve {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let points = self
+            .0
+            .iter()
+            .map(|(t, d)| format!("{}:{}", t, d))
+            .collect::<Vec<_>>();
+        write!(f, "{}", points.join(","))
+    }
+}
+
+/// The curve hwmon directory and the fans it has curves for.
+pub fn available() -> Option<(PathBuf, Vec<Fan>)> {
+    let dir = hwmon_dir().ok()?;
+    let fans = [Fan::Cpu, Fan::Gpu, Fan::Mid]
+        .into_iter()
+        .filter(|f| dir.join(format!("pwm{}_enable", f.pwm())).exists())
+        .collect();
+    Some((dir, fans))
+}
+
+fn hwmon_dir() -> Result<PathBuf, String> {
+    hwmon_devices()
+        .into_iter()
+        .find(|(_, name)| name == HWMON_NAME)
+        .map(|(dir, _)| dir)
+        .ok_or_else(|| "custom fan curves are not supported by this kernel".into())
+}
+
+/// The curve points and enable switches of every fan the hwmon device has.
+pub fn nodes() -> Vec<PathBuf> {
+    let Ok(dir) = hwmon_dir() else {
+        return Vec::new();
+    };
+    [Fan::Cpu, Fan::Gpu, Fan::Mid]
+        .iter()
+        .flat_map(|fan| fan_nodes(fan.pwm()))
+        .map(|node| dir.join(node))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// The point nodes of curve `pwm`, then its enable switch.
+fn fan_nodes(pwm: u32) -> impl Iterator<Item = String> {
+    (1..=POINTS)
+        .flat_map(move |point| {
+            ["temp", "pwm"].map(|kind| format!("pwm{}_auto_point{}_{}", pwm, point, kind))
+        })
+        .chain([format!("pwm{}_enable", pwm)])
+}
+
+fn state_path() -> PathBuf {
+    config::state_dir().join("fan-curves.toml")
+}
+
+fn load_curves() -> Result<Curves, String> {
+    sysfs::read_state(state_path())
+}
+
+fn save_curves(curves: &Curves) -> Result<(), String> {
+    let text = toml::to_string(curves).map_err(|e| e.to_string())?;
+    sysfs::write_state(state_path(), &text)
+}
+
+fn write_node(dir: &Path, node: String, value: impl ToString) -> Result<(), String> {
+    sysfs::write(dir.join(node), &value.to_string())
+}
+
+/// Writes `curve` and enables it. A write that fails puts back the curve the fan had, so a
+/// half-written one never drives it.
+fn write_curve(fan: Fan, curve: &Curve) -> Result<(), String> {
+    let dir = hwmon_dir()?;
+    let pwm = fan.pwm();
+    if !dir.join(format!("pwm{}_enable", pwm)).exists() {
+        return Err(format!("no {} fan curve on this machine", fan.as_str()));
+    }
+
+    let before: Vec<(String, String)> = fan_nodes(pwm)
+        .filter_map(|node| read_trimmed(&dir.join(&node)).map(|value| (node, value)))
+        .collect();
+    let result = write_points(&dir, pwm, curve);
+    if result.is_err() {
+        for (node, value) in before {
+            if let Err(e) = write_node(&dir, node, value) {
+                warn!("Failed to restore the {} fan curve: {}", fan.as_str(), e);
+            }
+        }
+    }
+    result
+}
+
+fn write_points(dir: &Path, pwm: u32, curve: &Curve) -> Result<(), String> {
+    for (i, (t, d)) in curve.0.iter().enumerate() {
+        let point = i + 1;
+        write_node(dir, format!("pwm{}_auto_point{}_temp", pwm, point), t)?;
+        let raw = (*d as u32 * 255).div_ceil(100);
+        write_node(dir, format!("pwm{}_auto_point{}_pwm", pwm, point), raw)?;
+    }
+    write_node(dir, format!("pwm{}_enable", pwm), 1)
+}
+
+fn current_profile() -> Result<PlatformProfile, String> {
+    let s = get_fan_profile()?;
+    PlatformProfile::parse(&s).ok_or_else(|| format!("unsupported active profile: {}", s))
+}
+
+/// Reads the curve stored for `profile`, or the live curve when no profile is given.
+pub fn get_fan_curve(fan: Fan, profile: Option<PlatformProfile>) -> Result<String, String> {
+    if let Some(p) = profile {
+        return Ok(load_curves()?
+            .get(p.as_str())
+            .and_then(|fans| fans.get(fan.as_str()))
+            .cloned()
+            .unwrap_or_else(|| "default".into()));
+    }
+
+    let dir = hwmon_dir()?;
+    let pwm = fan.pwm();
+    let mut points = Vec::with_capacity(POINTS);
+    for point in 1..=POINTS {
+        let read = |kind: &str| {
+            let path = dir.join(format!("pwm{}_auto_point{}_{}", pwm, point, kind));
+            read_trimmed(&path)
+                .and_then(|s| s.parse::<u32>().ok())
+                .ok_or_else(|| format!("failed to read {}", path.display()))
+        };
+        let t = read("temp")?;
+        let d = read("pwm")? * 100 / 255;
+        points.push(format!("{}:{}", t, d));
+    }
+    Ok(points.join(","))
+}
+
+/// Stores a curve for `profile` (default: the active one) and applies it if that profile is active.
+/// The curve `default` drops the stored curve and returns the fan to firmware control.
+pub fn set_fan_curve(
+    fan: Fan,
+    curve: &str,
+    profile: Option<PlatformProfile>,
+) -> Result<String, String> {
+    let _settings = transaction::lock();
+    let active = current_profile();
+    let profile = match profile {
+        Some(p) => p,
+        None => active.clone()?,
+    };
+    let is_active = active.is_ok_and(|a| a == profile);
+    let mut curves = load_curves()?;
+
+    if curve == "default" {
+        if let Some(fans) = curves.get_mut(profile.as_str()) {
+            fans.remove(fan.as_str());
+        }
+        save_curves(&curves)?;
+        if is_active {
+            let dir = hwmon_dir()?;
+            write_node(&dir, format!("pwm{}_enable", fan.pwm()), 2)?;
+        }
+        return Ok(format!(
+            "{} fan curve for {} reset to default",
+            fan.as_str(),
+            profile.as_str()
+        ));
+    }
+
+    let curve = Curve::parse(curve)?;
+    if is_active {
+        write_curve(fan, &curve)?;
+    }
+    curves
+        .entry(profile.as_str().to_string())
+        .or_default()
+        .insert(fan.as_str().to_string(), curve.to_string());
+    save_curves(&curves)?;
+
+    Ok(format!(
+        "{} fan curve for {} set to {}",
+        fan.as_str(),
+        profile.as_str(),
+        curve
+    ))
+}
+
+/// Re-applies the stored curves after a profile change, which resets them in firmware.
+pub fn reapply(profile: PlatformProfile) {
+    let curves = match load_curves() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Failed to load fan curves: {}", e);
+            return;
+        }
+    };
+    let Some(fans) = curves.get(profile.as_str()) else {
+        return;
+    };
+
+    for (name, curve) in fans {
+        let (Some(fan), Ok(curve)) = (Fan::parse(name), Curve::parse(curve)) else {
+            warn!("Ignoring invalid stored fan curve for {}", name);
+            continue;
+        };
+        if let Err(e) = write_curve(fan, &curve) {
+            warn!("Failed to reapply {} fan curve: {}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_validates_curves() {
+        let curve = Curve::parse("30:0,40:10,50:20,60:35,70:50,80:70,90:90,100:100%").unwrap();
+        assert_eq!(
+            curve.to_string(),
+            "30:0,40:10,50:20,60:35,70:50,80:70,90:90,100:100"
+        );
+
+        let cases = [
+            ("30:0,40:10,50:20", "fan curve needs exactly 8 points"),
+            (
+                "30:0,40:10,50:20,60:35,70:50,80:70,90:90,100:100,100:100",
+                "fan curve needs exactly 8 points",
+            ),
+            (
+                "30:0,40:10,50:20,60:35,70:50,80:70,90:90,101:100",
+                "curve temperature 101 above 100",
+            ),
+            (
+                "30:0,40:10,50:20,60:35,70:50,80:70,90:90,100:101",
+                "curve duty 101 above 100%",
+            ),
+            (
+                "30:0,40:10,50:20,45:35,70:50,80:70,90:90,100:100",
+                "fan curve must be monotonic",
+            ),
+            (
+                "30:0,40:10,50:20,60:15,70:50,80:70,90:90,100:100",
+                "fan curve must be monotonic",
+            ),
+            (
+                "30:0,40:10,50:20,60,70:50,80:70,90:90,100:100",
+                "invalid curve point: 60",
+            ),
+            (
+                "30:0,40:10,50:20,hot:35,70:50,80:70,90:90,100:100",
+                "invalid curve temperature: hot",
+            ),
+        ];
+        for (input, error) in cases {
+            assert_eq!(
+                Curve::parse(input).err().as_deref(),
+                Some(error),
+                "{}",
+                input
+            );
+        }
+    }
+}
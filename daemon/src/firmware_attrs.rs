@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::sensors::read_trimmed;
+use crate::sysfs;
+use crate::transaction;
+
+pub const ATTRIBUTES_DIR: &str = "/sys/class/firmware-attributes/asus-armoury/attributes";
+
+pub enum AttrKind {
+    Integer { min: i64, max: i64, step: i64 },
+    Enumeration(Vec<String>),
+    String,
+}
+
+pub struct FirmwareAttr {
+    pub name: String,
+    pub display_name: String,
+    pub current: String,
+    pub kind: AttrKind,
+}
+
+impl FirmwareAttr {
+    fn load(dir: &Path) -> Option<FirmwareAttr> {
+        let name = dir.file_name()?.to_str()?.to_string();
+        let read = |node: &str| read_trimmed(&dir.join(node));
+
+        let kind = match read("type")?.as_str() {
+            "integer" => AttrKind::Integer {
+                min: read("min_value")?.parse().ok()?,
+                max: read("max_value")?.parse().ok()?,
+                step: read("scalar_increment")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(1),
+            },
+            "enumeration" => AttrKind::Enumeration(
+                read("possible_values")?
+                    .split(';')
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+                    .collect(),
+            ),
+            _ => AttrKind::String,
+        };
+
+        Some(FirmwareAttr {
+            display_name: read("display_name").unwrap_or_else(|| name.clone()),
+            current: read("current_value")?,
+            name,
+            kind,
+        })
+    }
+
+    fn validate(&self, value: &str) -> Result<(), String> {
+        match &self.kind {
+            AttrKind::Integer { min, max, step } => {
+                let n = value
+                    .parse::<i64>()
+                    .map_err(|_| format!("{} expects an integer", self.name))?;
+                if n < *min || n > *max {
+                    return Err(format!("{} out of range ({}-{})", self.name, min, max));
+                }
+                if *step > 1 && (n - min) % step != 0 {
+                    return Err(format!(
+                        "{} must be a multiple of {} from {}",
+                        self.name, step, min
+                    ));
+                }
+                Ok(())
+            }
+            AttrKind::Enumeration(values) => {
+                if values.iter().any(|v| v == value) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{} expects one of {}",
+                        self.name,
+                        values.join(", ")
+                    ))
+                }
+            }
+            AttrKind::String => Ok(()),
+        }
+    }
+
+    /// One line: name, then `key=value` metadata, with the free-form display name last and a
+    /// current value containing spaces in double quotes.
+    fn describe(&self) -> String {
+        let kind = match &self.kind {
+            AttrKind::Integer { min, max, step } => {
+                format!("type=integer min={} max={} step={}", min, max, step)
+            }
+            AttrKind::Enumeration(values) => {
+                format!("type=enumeration values={}", values.join(";"))
+            }
+            AttrKind::String => "type=string".into(),
+        };
+        // String attributes can hold spaces; quote those like `get presets` does.
+        let current = match self.current.contains(char::is_whitespace) {
+            true => format!("\"{}\"", self.current),
+            false => self.current.clone(),
+        };
+        format!(
+            "{}: {} current={} display_name={}",
+            self.name, kind, current, self.display_name
+        )
+    }
+}
+
+/// The directory of attribute `name`, refusing names that would lead out of [`ATTRIBUTES_DIR`].
+fn attr_dir(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(format!("invalid firmware attribute: {}", name));
+    }
+    Ok(sysfs::path(ATTRIBUTES_DIR).join(name))
+}
+
+pub fn attributes() -> Vec<FirmwareAttr> {
+    let Ok(entries) = fs::read_dir(sysfs::path(ATTRIBUTES_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut attrs: Vec<FirmwareAttr> = entries
+        .flatten()
+        .filter_map(|entry| FirmwareAttr::load(&entry.path()))
+        .collect();
+    attrs.sort_by(|a, b| a.name.cmp(&b.name));
+    attrs
+}
+
+/// The `current_value` node of every attribute.
+pub fn nodes() -> Vec<PathBuf> {
+    attributes()
+        .iter()
+        .filter_map(|a| attr_dir(&a.name).ok())
+        .map(|dir| dir.join("current_value"))
+        .collect()
+}
+
+pub fn get_firmware_attrs() -> Result<String, String> {
+    let attrs = attributes();
+    if attrs.is_empty() {
+        return Err("no asus-armoury firmware attributes found".into());
+    }
+
+    Ok(attrs
+        .iter()
+        .map(FirmwareAttr::describe)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+pub fn current_value(name: &str) -> Result<String, String> {
+    FirmwareAttr::load(&attr_dir(name)?)
+        .map(|a| a.current)
+        .ok_or_else(|| format!("unknown firmware attribute: {}", name))
+}
+
+pub fn set_firmware_attr(name: &str, value: &str) -> Result<String, String> {
+    let _settings = transaction::lock();
+    let dir = attr_dir(name)?;
+    let attr =
+        FirmwareAttr::load(&dir).ok_or_else(|| format!("unknown firmware attribute: {}", name))?;
+    attr.validate(value)?;
+
+    sysfs::write(dir.join("current_value"), value)?;
+    Ok(format!("{} set to {}", name, value))
+}
@@ -0,0 +1,19 @@
+use crate::backend;
+use crate::transaction;
+
+pub const KBD_BACKLIGHT_DIR: &str = "/sys/class/leds/asus::kbd_backlight";
+
+pub fn get_keyboard_backlight() -> Result<String, String> {
+    backend::get().keyboard_brightness().map(|v| v.to_string())
+}
+
+pub fn set_keyboard_backlight(value: u32) -> Result<String, String> {
+    let _settings = transaction::lock();
+    let max = backend::get().max_keyboard_brightness()?;
+    if value > max {
+        return Err(format!("keyboard backlight out of range (0-{})", max));
+    }
+
+    backend::get().set_keyboard_brightness(value)?;
+    Ok(format!("Keyboard backlight set to {}", value))
+}
@@ -0,0 +1,27 @@
+use std::env;
+
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+
+/// Logs to the journal when started by systemd or detached, and to stderr in the foreground.
+/// `level` comes from `--log-level` and overrides `RUST_LOG`; without either only `info` and
+/// above are logged. Journal entries carry event and span fields as upper-case fields, e.g.
+/// `COMMAND=`.
+pub fn init(level: Option<&str>, foreground: bool) -> Result<(), String> {
+    let filter = match level {
+        Some(l) => EnvFilter::try_new(l).map_err(|e| format!("invalid log level {}: {}", l, e))?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let registry = tracing_subscriber::registry().with(filter);
+
+    if (!foreground || env::var_os("JOURNAL_STREAM").is_some())
+        && let Ok(journald) = tracing_journald::layer()
+    {
+        registry.with(journald.with_field_prefix(None)).init();
+    } else {
+        registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init();
+    }
+    Ok(())
+}
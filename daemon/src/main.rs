@@ -1,40 +1,204 @@
-use std::io::{Read, Write};
-use std::os::unix::net::UnixListener;
+mod app_profiles;
+mod asusd;
+mod backend;
+mod capabilities;
+mod config;
+mod conflicts;
+mod cpu_tuning;
+mod dbus;
+mod fan_curve;
+mod firmware_attrs;
+mod keyboard;
+mod logging;
+mod metrics;
+mod options;
+mod power_knobs;
+mod power_profiles;
+mod presets;
+mod profile;
+mod quirks;
+mod sampler;
+mod sandbox;
+mod sensors;
+mod simulate;
+mod status;
+mod sysfs;
+mod transaction;
+
 use std::fs;
+use std::io::{Read, Write};
 use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+
+use asus_control_protocol::PROTOCOL_VERSION;
+use tracing::{debug, error, info, info_span, warn};
+
+use options::Options;
+use profile::{PlatformProfile, get_fan_profile, set_fan_mode};
+
+/// How long a client may take to send its request or accept the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests longer than this are cut off; even a large batch is far smaller.
+const MAX_REQUEST: u64 = 64 * 1024;
+
+const SET_TARGETS: &[&str] = &[
+    "battery-threshold",
+    "profile",
+    "fan-curve",
+    "firmware-attr",
+    "keyboard-backlight",
+];
+
+const GET_TARGETS: &[&str] = &[
+    "battery-threshold",
+    "profile",
+    "profile-trigger",
+    "fan-speed-rpm",
+    "fans",
+    "temps",
+    "fan-curve",
+    "firmware-attrs",
+    "capabilities",
+    "system-info",
+    "cpu-tuning",
+    "profile-details",
+    "keyboard-backlight",
+    "presets",
+    "status",
+    "sensor-history",
+];
+
+const VERBS: &[&str] = &[
+    "hello",
+    "get",
+    "set",
+    "apply",
+    "save",
+    "delete",
+    "batch",
+    "subscribe",
+];
+
+/// The uid of the process on the other end of `stream`.
+fn peer_uid(stream: &UnixStream) -> Option<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes and sized for SO_PEERCRED.
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    (ret == 0).then_some(cred.uid)
+}
 
-#[derive(Debug)]
-enum PlatformProfile {
-    Quiet,
-    Balanced,
-    Performance,
+/// Forks into the background: the parent exits and the child gets its own session, with
+/// stdio on /dev/null. Must run before any thread is spawned.
+fn daemonize() -> std::io::Result<()> {
+    // SAFETY: the process is still single-threaded, so the child is a consistent copy.
+    match unsafe { libc::fork() } {
+        -1 => return Err(std::io::Error::last_os_error()),
+        0 => {}
+        _ => std::process::exit(0),
+    }
+    // SAFETY: setsid and dup2 only affect this process's session and file descriptors.
+    unsafe {
+        libc::setsid();
+        let null = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/null")?;
+        for fd in 0..3 {
+            libc::dup2(null.as_raw_fd(), fd);
+        }
+    }
+    std::env::set_current_dir("/")
 }
 
 fn main() -> std::io::Result<()> {
-    let socket_path = "/run/asus-control-daemon.sock";
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    if options.check_config {
+        match config::load(&options.config) {
+            Ok(_) => println!("{}: ok", options.config.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
+    logging::init(options.log_level.as_deref(), options.foreground)
+        .map_err(std::io::Error::other)?;
+    if let Some(root) = options.sysfs_root {
+        sysfs::set_root(root);
+    }
+    let simulated = match options.simulate {
+        true => match simulate::init() {
+            Ok(root) => {
+                config::set_state_dir(root.join("state"));
+                Some(root)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        false => None,
+    };
+    quirks::init();
+    if let Err(e) = config::init(&options.config) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let socket_path = &options.socket;
     let _ = std::fs::remove_file(socket_path);
 
     let listener = UnixListener::bind(socket_path)?;
     fs::set_permissions(socket_path, fs::Permissions::from_mode(0o660))?;
-    println!("asus-control-daemon listening on {}", socket_path);
+    if !options.foreground {
+        daemonize()?;
+    }
+    info!("asus-control-daemon listening on {}", socket_path.display());
+    let metrics = metrics::bind(&config::get().metrics);
+    dbus::init();
+    if !options.no_sandbox
+        && let Err(e) = sandbox::enter(&options.user)
+    {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(root) = simulated {
+        simulate::spawn(root);
+    }
+    app_profiles::spawn(&config::get().app_profiles);
+    sampler::spawn(&config::get().sampler);
+    metrics::spawn(metrics);
+    dbus::spawn(&config::get().dbus);
+    conflicts::spawn();
+    presets::apply_defaults();
 
     for stream in listener.incoming() {
         match stream {
-            Ok(mut stream) => {
-                let mut buf = String::new();
-                if let Err(e) = stream.read_to_string(&mut buf) {
-                    eprintln!("Error reading from client: {}", e);
-                    continue;
-                }
-
-                let response = handle_command(&buf);
-                if let Err(e) = stream.write_all(response.as_bytes()) {
-                    eprintln!("Error writing to client: {}", e);
-                }
-            }
+            Ok(stream) => serve(stream),
             Err(e) => {
-                eprintln!("Connection error: {}", e);
+                error!("Connection error: {}", e);
             }
         }
     }
@@ -42,8 +206,68 @@ fn main() -> std::io::Result<()> {
     Ok(())
 }
 
+/// Reads one request from a client and answers it, inside a span carrying the command and
+/// the client's uid.
+fn serve(mut stream: UnixStream) {
+    // Clients are served one at a time, so a stalled or flooding one must not hold the rest up.
+    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+    let mut buf = String::new();
+    if let Err(e) = (&mut stream).take(MAX_REQUEST).read_to_string(&mut buf) {
+        warn!("Error reading from client: {}", e);
+        return;
+    }
+
+    let span = info_span!(
+        "request",
+        command = buf.trim(),
+        peer_uid = peer_uid(&stream)
+    );
+    let _guard = span.enter();
+
+    if buf.trim() == "subscribe" {
+        debug!("Client subscribed to sensor updates");
+        sampler::subscribe(stream);
+        return;
+    }
+
+    let response = handle_command(&buf);
+    if response.starts_with("error:") {
+        info!(response = response.as_str(), "Request failed");
+    } else {
+        debug!(response = response.as_str(), "Request handled");
+    }
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        warn!("Error writing to client: {}", e);
+    }
+}
+
 fn handle_command(cmd: &str) -> String {
     let cmd = cmd.trim();
+    if let Some(cmd) = strip_dry_run(cmd) {
+        let (response, writes) = sysfs::dry_run(|| handle_command(&cmd));
+        if response.starts_with("error:") {
+            return response;
+        }
+        let mut lines = vec![format!("dry-run: {}", response)];
+        lines.extend(writes);
+        return lines.join("\n");
+    }
+
+    // Trimming leaves a batch without items as a bare `batch`.
+    if let Some(body) = cmd
+        .strip_prefix("batch")
+        .filter(|b| b.is_empty() || b.starts_with('\n'))
+    {
+        let commands: Vec<String> = body
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect();
+        return transaction::run(&commands).1;
+    }
+
     let mut parts = cmd.split_whitespace();
     let verb = match parts.next() {
         Some(v) => v,
@@ -51,113 +275,349 @@ fn handle_command(cmd: &str) -> String {
     };
 
     match verb {
-        "set" => {
-            match parts.next() {
-                Some("battery-threshold") => {
-                    if let Some(arg) = parts.next() {
-                        if let Ok(n) = arg.parse::<i32>() {
-                            return match set_battery_threshold(n) {
-                                Ok(m) => m,
-                                Err(e) => format!("error: {}", e),
-                            };
-                        } else {
-                            return "error: invalid battery threshold".into();
-                        }
+        "hello" => hello(),
+        "set" => match parts.next() {
+            Some("battery-threshold") => match parts.next() {
+                Some(arg) => match arg.parse::<i32>() {
+                    Ok(n) => set_battery_threshold(n).unwrap_or_else(|e| format!("error: {}", e)),
+                    Err(_) => "error: invalid battery threshold".into(),
+                },
+                None => "error: battery-threshold requires a value".into(),
+            },
+            Some("profile") => match parts.next() {
+                Some(arg) => match PlatformProfile::parse(arg) {
+                    Some(p) => set_fan_mode(p).unwrap_or_else(|e| format!("error: {}", e)),
+                    None => "error: invalid profile".into(),
+                },
+                None => "error: profile requires an argument".into(),
+            },
+            Some("fan-curve") => match (parts.next().map(fan_curve::Fan::parse), parts.next()) {
+                (Some(Some(fan)), Some(curve)) => match parts.next().map(PlatformProfile::parse) {
+                    Some(None) => "error: invalid profile".into(),
+                    profile => fan_curve::set_fan_curve(fan, curve, profile.flatten())
+                        .unwrap_or_else(|e| format!("error: {}", e)),
+                },
+                (Some(None), _) => "error: invalid fan - use cpu, gpu or mid".into(),
+                _ => "error: fan-curve requires a fan and a curve".into(),
+            },
+            Some("firmware-attr") => {
+                let name = parts.next();
+                let value = parts.collect::<Vec<_>>().join(" ");
+                match name {
+                    Some(name) if !value.is_empty() => {
+                        firmware_attrs::set_firmware_attr(name, &value)
+                            .unwrap_or_else(|e| format!("error: {}", e))
                     }
-                    return "error: battery-threshold requires a value".into();
+                    _ => "error: firmware-attr requires a name and a value".into(),
                 }
-                Some("profile") => {
-                    if let Some(arg) = parts.next() {
-                        let profile = match arg {
-                            "quiet" => Some(PlatformProfile::Quiet),
-                            "balanced" => Some(PlatformProfile::Balanced),
-                            "performance" => Some(PlatformProfile::Performance),
-                            _ => None,
-                        };
-                        if let Some(p) = profile {
-                            return match set_fan_mode(p) {
-                                Ok(m) => m,
-                                Err(e) => format!("error: {}", e),
-                            };
-                        } else {
-                            return "error: invalid profile".into();
-                        }
-                    }
-                    return "error: profile requires an argument".into();
-                }
-                Some(other) => return format!("error: unknown set target: {}", other),
-                None => return "error: set requires a target".into(),
             }
-        }
-        "get" => {
-            match parts.next() {
-                Some("battery-threshold") => {
-                    return match get_battery_threshold() {
-                        Ok(m) => m,
-                        Err(e) => format!("error: {}", e),
-                    };
-                }
-                Some("profile") => {
-                    return match get_fan_profile() {
-                        Ok(m) => m,
-                        Err(e) => format!("error: {}", e),
-                    };
-                }
-                Some("fan-speed-rpm") => {
-                    return match get_fan_speed_rpm() {
-                        Ok(m) => m,
-                        Err(e) => format!("error: {}", e),
-                    };
-                }
-                Some(other) => return format!("error: unknown get target: {}", other),
-                None => return "error: get requires a target".into(),
+            Some("keyboard-backlight") => match parts.next() {
+                Some(arg) => match arg.parse::<u32>() {
+                    Ok(n) => keyboard::set_keyboard_backlight(n)
+                        .unwrap_or_else(|e| format!("error: {}", e)),
+                    Err(_) => "error: invalid keyboard backlight level".into(),
+                },
+                None => "error: keyboard-backlight requires a value".into(),
+            },
+            Some(other) => format!("error: unknown set target: {}", other),
+            None => "error: set requires a target".into(),
+        },
+        "get" => match parts.next() {
+            Some("battery-threshold") => {
+                get_battery_threshold().unwrap_or_else(|e| format!("error: {}", e))
             }
-        }
-        _ => {
-            return "error: unknown command - use 'get' or 'set'".into();
-        }
+            Some("profile") => get_fan_profile().unwrap_or_else(|e| format!("error: {}", e)),
+            Some("profile-trigger") => app_profiles::current_trigger(),
+            Some("fan-speed-rpm") => {
+                get_fan_speed_rpm().unwrap_or_else(|e| format!("error: {}", e))
+            }
+            Some("fans") => sensors::get_fans(&sampler::latest().fans)
+                .unwrap_or_else(|e| format!("error: {}", e)),
+            Some("temps") => sensors::get_temps(&sampler::latest().temps)
+                .unwrap_or_else(|e| format!("error: {}", e)),
+            Some("fan-curve") => match parts.next().map(fan_curve::Fan::parse) {
+                Some(Some(fan)) => match parts.next().map(PlatformProfile::parse) {
+                    Some(None) => "error: invalid profile".into(),
+                    profile => fan_curve::get_fan_curve(fan, profile.flatten())
+                        .unwrap_or_else(|e| format!("error: {}", e)),
+                },
+                Some(None) => "error: invalid fan - use cpu, gpu or mid".into(),
+                None => "error: fan-curve requires a fan".into(),
+            },
+            Some("firmware-attrs") => {
+                firmware_attrs::get_firmware_attrs().unwrap_or_else(|e| format!("error: {}", e))
+            }
+            Some("capabilities") => capabilities::get_capabilities(),
+            Some("system-info") => quirks::get_system_info(),
+            Some("cpu-tuning") => cpu_tuning::get_cpu_tuning(),
+            Some("profile-details") => {
+                power_knobs::get_profile_details().unwrap_or_else(|e| format!("error: {}", e))
+            }
+            Some("keyboard-backlight") => {
+                keyboard::get_keyboard_backlight().unwrap_or_else(|e| format!("error: {}", e))
+            }
+            Some("presets") => presets::get_presets().unwrap_or_else(|e| format!("error: {}", e)),
+            Some("status") => status::get_status(),
+            Some("sensor-history") => {
+                sampler::get_sensor_history().unwrap_or_else(|e| format!("error: {}", e))
+            }
+            Some(other) => format!("error: unknown get target: {}", other),
+            None => "error: get requires a target".into(),
+        },
+        "apply" | "save" | "delete" => match (parts.next(), parts.next()) {
+            (Some("preset"), Some(name)) => match verb {
+                "apply" => presets::apply_preset(name),
+                "save" => presets::save_preset(name),
+                _ => presets::delete_preset(name),
+            }
+            .unwrap_or_else(|e| format!("error: {}", e)),
+            (Some("preset"), None) => format!("error: {} preset requires a name", verb),
+            _ => format!("error: {} requires a preset", verb),
+        },
+        "subscribe" => "error: subscribe takes no arguments".into(),
+        "simulate" => match (parts.next(), parts.next()) {
+            (Some("ac"), Some(state)) => {
+                simulate::set_ac(state).unwrap_or_else(|e| format!("error: {}", e))
+            }
+            _ => "error: simulate requires ac <online|offline|toggle>".into(),
+        },
+        _ => "error: unknown command - use 'hello', 'get', 'set', 'apply', 'save', 'delete' or \
+              'subscribe'"
+            .into(),
     }
 }
 
-fn get_battery_threshold() -> Result<String, String> {
-    let path = "/sys/class/power_supply/BAT0/charge_control_end_threshold";
-    let s = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
-    Ok(format!("{}", s.trim()))
+fn hello() -> String {
+    let mut verbs = VERBS.to_vec();
+    if simulate::active() {
+        verbs.push("simulate");
+    }
+    [
+        format!("daemon: asus-control-daemon {}", env!("CARGO_PKG_VERSION")),
+        format!("protocol: {}", PROTOCOL_VERSION),
+        format!("verbs: {}", verbs.join(" ")),
+        format!("get: {}", GET_TARGETS.join(" ")),
+        format!("set: {}", SET_TARGETS.join(" ")),
+    ]
+    .join("\n")
+}
+
+/// Removes a `--dry-run` flag following the verb, e.g. `set --dry-run profile quiet`.
+fn strip_dry_run(cmd: &str) -> Option<String> {
+    let (first, rest) = cmd.split_once('\n').unwrap_or((cmd, ""));
+    let mut tokens: Vec<&str> = first.split_whitespace().collect();
+    if tokens.get(1) != Some(&"--dry-run") {
+        return None;
+    }
+    tokens.remove(1);
+    let mut stripped = tokens.join(" ");
+    if !rest.is_empty() {
+        stripped.push('\n');
+        stripped.push_str(rest);
+    }
+    Some(stripped)
 }
 
-fn get_fan_profile() -> Result<String, String> {
-    let path = "/sys/firmware/acpi/platform_profile";
-    let s = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
-    Ok(format!("{}", s.trim()))
+fn get_battery_threshold() -> Result<String, String> {
+    backend::get().battery_threshold().map(|v| v.to_string())
 }
 
 fn get_fan_speed_rpm() -> Result<String, String> {
-    let path = "/sys/class/hwmon/hwmon1/fan1_input";
-    let s = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
-    Ok(format!("{}", s.trim()))
+    sampler::latest()
+        .fans
+        .first()
+        .map(|f| f.rpm.to_string())
+        .ok_or_else(|| "no fans found".into())
 }
 
 fn set_battery_threshold(value: i32) -> Result<String, String> {
+    let _settings = transaction::lock();
     if !(0..=100).contains(&value) {
         return Err("threshold out of range (0-100)".into());
     }
+    if let Some(values) = &quirks::get().threshold_values
+        && !values.contains(&value)
+    {
+        let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        return Err(format!(
+            "this model only accepts thresholds {}",
+            values.join(", ")
+        ));
+    }
 
-    let path = "/sys/class/power_supply/BAT0/charge_control_end_threshold";
-    std::fs::write(path, value.to_string())
-        .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    backend::get().set_battery_threshold(value as u32)?;
     Ok(format!("Battery threshold set to {}", value))
 }
 
-fn set_fan_mode(profile: PlatformProfile) -> Result<String, String> {
-    let desc = match profile {
-        PlatformProfile::Quiet => "quiet",
-        PlatformProfile::Balanced => "balanced",
-        PlatformProfile::Performance => "performance",
-    };
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use super::*;
+    use crate::backend::Memory;
+
+    /// Points sysfs and the state directory at an empty tree whose DMI matches a model that
+    /// only takes thresholds 60, 80 and 100, then gives the calling test its own laptop.
+    pub(crate) fn setup() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let root =
+                std::env::temp_dir().join(format!("asus-control-test-{}", std::process::id()));
+            let dmi = root.join("sys/class/dmi/id");
+            fs::create_dir_all(&dmi).unwrap();
+            fs::write(dmi.join("product_name"), "VivoBook_ASUSLaptop X515EA\n").unwrap();
+            sysfs::set_root(root.clone());
+            config::set_state_dir(root.join("state"));
+            quirks::init();
+        });
+        backend::set_for_thread(Box::new(Memory::new()));
+    }
+
+    #[test]
+    fn sets_and_gets_profile() {
+        setup();
+        assert_eq!(
+            handle_command("set profile quiet"),
+            "Profile set to quiet (memory)"
+        );
+        assert_eq!(handle_command("get profile"), "quiet");
+        assert_eq!(
+            handle_command("set profile turbo"),
+            "error: invalid profile"
+        );
+    }
 
-    let path = "/sys/firmware/acpi/platform_profile";
-    std::fs::write(path, desc)
-        .map_err(|e| format!("failed to write {}: {}", path, e))?;
+    #[test]
+    fn checks_battery_threshold_range_and_quirks() {
+        setup();
+        assert_eq!(
+            handle_command("set battery-threshold 101"),
+            "error: threshold out of range (0-100)"
+        );
+        assert_eq!(
+            handle_command("set battery-threshold 70"),
+            "error: this model only accepts thresholds 60, 80, 100"
+        );
+        assert_eq!(
+            handle_command("set battery-threshold 80"),
+            "Battery threshold set to 80"
+        );
+        assert_eq!(handle_command("get battery-threshold"), "80");
+    }
+
+    #[test]
+    fn bounds_keyboard_backlight() {
+        setup();
+        assert_eq!(
+            handle_command("set keyboard-backlight 4"),
+            "error: keyboard backlight out of range (0-3)"
+        );
+        assert_eq!(
+            handle_command("set keyboard-backlight 3"),
+            "Keyboard backlight set to 3"
+        );
+        assert_eq!(handle_command("get keyboard-backlight"), "3");
+    }
 
-    Ok(format!("Profile set to {}", desc))
+    #[test]
+    fn rolls_back_failed_batch() {
+        setup();
+        let response = handle_command(
+            "batch\nset profile performance\nset keyboard-backlight 2\nset battery-threshold 70",
+        );
+        assert_eq!(
+            response.lines().collect::<Vec<_>>(),
+            [
+                "error: transaction failed at item 3, 2 change(s) rolled back",
+                "rolled-back: set profile performance",
+                "rolled-back: set keyboard-backlight 2",
+                "failed: set battery-threshold 70: this model only accepts thresholds 60, 80, 100",
+            ]
+        );
+        assert_eq!(handle_command("get profile"), "balanced");
+        assert_eq!(handle_command("get keyboard-backlight"), "0");
+    }
+
+    #[test]
+    fn dry_run_leaves_settings_alone() {
+        setup();
+        assert_eq!(
+            handle_command("set --dry-run profile performance"),
+            "dry-run: Profile set to performance (memory)\n\
+             would write \"performance\" to memory:profile"
+        );
+        assert_eq!(handle_command("get profile"), "balanced");
+    }
+
+    #[test]
+    fn strips_dry_run_after_the_verb() {
+        let cases = [
+            ("set --dry-run profile quiet", Some("set profile quiet")),
+            (
+                "batch --dry-run\nset profile quiet",
+                Some("batch\nset profile quiet"),
+            ),
+            ("set profile quiet", None),
+            ("set profile --dry-run quiet", None),
+            ("--dry-run set profile quiet", None),
+            ("batch\nset --dry-run profile quiet", None),
+        ];
+        for (input, stripped) in cases {
+            assert_eq!(strip_dry_run(input).as_deref(), stripped, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn dry_runs_a_whole_batch() {
+        setup();
+        assert_eq!(
+            handle_command("batch --dry-run\nset profile performance\nset keyboard-backlight 2")
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "dry-run: ok: 2 settings applied",
+                "ok: set profile performance: Profile set to performance (memory)",
+                "ok: set keyboard-backlight 2: Keyboard backlight set to 2",
+                "would write \"performance\" to memory:profile",
+                "would write \"2\" to memory:keyboard-backlight",
+            ]
+        );
+        assert_eq!(handle_command("get profile"), "balanced");
+        assert_eq!(handle_command("get keyboard-backlight"), "0");
+
+        assert_eq!(
+            handle_command("batch\nset --dry-run profile performance\nset keyboard-backlight 2")
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "error: transaction failed at item 1, 0 change(s) rolled back",
+                "failed: set --dry-run profile performance: --dry-run cannot be part of a transaction",
+                "skipped: set keyboard-backlight 2",
+            ]
+        );
+        assert_eq!(handle_command("get keyboard-backlight"), "0");
+    }
+
+    #[test]
+    fn rejects_malformed_batches() {
+        setup();
+        assert_eq!(handle_command("batch"), "error: empty transaction");
+        assert_eq!(handle_command("batch\n\n  \n"), "error: empty transaction");
+        assert_eq!(
+            handle_command("batch\nfrobnicate\nset profile quiet")
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "error: transaction failed at item 1, 0 change(s) rolled back",
+                "failed: frobnicate: only set commands can be part of a transaction",
+                "skipped: set profile quiet",
+            ]
+        );
+        assert_eq!(
+            handle_command("batch\nset profile turbo"),
+            "error: transaction failed at item 1, 0 change(s) rolled back\n\
+             failed: set profile turbo: invalid profile"
+        );
+        assert_eq!(handle_command("get profile"), "balanced");
+    }
 }
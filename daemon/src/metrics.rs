@@ -0,0 +1,281 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::app_profiles;
+use crate::backend;
+use crate::config::MetricsConfig;
+use crate::profile::{PlatformProfile, get_fan_profile};
+use crate::sampler;
+use crate::sensors;
+use crate::sysfs;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// How long a client may take to send its request before it's dropped, so a stalled one
+/// can't hold up the others.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the exporter listens: a loopback TCP address or a unix socket path.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl TryFrom<String> for Listen {
+    type Error = String;
+
+    /// Accepts `127.0.0.1:9100`-style loopback addresses and absolute socket paths.
+    fn try_from(s: String) -> Result<Listen, String> {
+        if s.starts_with('/') {
+            return Ok(Listen::Unix(PathBuf::from(s)));
+        }
+        let addr = s
+            .parse::<SocketAddr>()
+            .map_err(|_| format!("invalid metrics address: {}", s))?;
+        if !addr.ip().is_loopback() {
+            return Err(format!("metrics address must be on localhost: {}", s));
+        }
+        Ok(Listen::Tcp(addr))
+    }
+}
+
+/// A bound metrics listener, waiting for [`spawn`] to serve it.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Binds the configured address. Runs before the sandbox, so the socket is created while the
+/// daemon can still write its directory, and owned like the daemon's own socket.
+pub fn bind(config: &MetricsConfig) -> Option<Listener> {
+    match config.listen.as_ref()? {
+        Listen::Tcp(addr) => match TcpListener::bind(addr) {
+            Ok(listener) => {
+                info!("Serving metrics on http://{}/metrics", addr);
+                Some(Listener::Tcp(listener))
+            }
+            Err(e) => {
+                warn!("Failed to bind metrics address {}: {}", addr, e);
+                None
+            }
+        },
+        Listen::Unix(path) => {
+            let _ = fs::remove_file(path);
+            match UnixListener::bind(path) {
+                Ok(listener) => {
+                    let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o660));
+                    info!("Serving metrics on {}", path.display());
+                    Some(Listener::Unix(listener))
+                }
+                Err(e) => {
+                    warn!("Failed to bind metrics socket {}: {}", path.display(), e);
+                    None
+                }
+            }
+        }
+    }
+}
+
+pub fn spawn(listener: Option<Listener>) {
+    let Some(listener) = listener else {
+        return;
+    };
+    thread::spawn(move || match listener {
+        Listener::Tcp(listener) => {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                serve(stream);
+            }
+        }
+        Listener::Unix(listener) => {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                serve(stream);
+            }
+        }
+    });
+}
+
+/// Answers one HTTP request with the current metrics, whatever the request path.
+fn serve<S: Read + Write>(mut stream: S) {
+    let mut reader = BufReader::new(&mut stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+        if line == "\r\n" || line == "\n" {
+            break;
+        }
+        line.clear();
+    }
+
+    let body = render();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        CONTENT_TYPE,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        warn!("Error writing metrics: {}", e);
+    }
+}
+
+/// Escapes a label value for the exposition format.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+}
+
+/// The full OpenMetrics exposition, ending with `# EOF`.
+pub fn render() -> String {
+    let mut out = String::new();
+
+    let active = get_fan_profile().ok();
+    header(
+        &mut out,
+        "asus_platform_profile",
+        "gauge",
+        "Whether each platform profile is the active one.",
+    );
+    for p in [
+        PlatformProfile::Quiet,
+        PlatformProfile::Balanced,
+        PlatformProfile::Performance,
+    ] {
+        let value = active.as_deref() == Some(p.as_str());
+        let _ = writeln!(
+            out,
+            "asus_platform_profile{{profile=\"{}\"}} {}",
+            p.as_str(),
+            value as u8
+        );
+    }
+
+    header(
+        &mut out,
+        "asus_battery_charge_threshold_percent",
+        "gauge",
+        "Battery charge control thresholds.",
+    );
+    let backend = backend::get();
+    let thresholds = [
+        ("start", backend.battery().start_threshold),
+        ("end", backend.battery_threshold().ok()),
+    ];
+    for (kind, value) in thresholds {
+        if let Some(v) = value {
+            let _ = writeln!(
+                out,
+                "asus_battery_charge_threshold_percent{{threshold=\"{}\"}} {}",
+                kind, v
+            );
+        }
+    }
+
+    let sample = sampler::latest();
+    header(&mut out, "asus_fan_rpm", "gauge", "Fan speeds.");
+    for fan in &sample.fans {
+        let _ = writeln!(
+            out,
+            "asus_fan_rpm{{fan=\"{}\"}} {}",
+            label(&fan.label),
+            fan.rpm
+        );
+    }
+
+    header(
+        &mut out,
+        "asus_temperature_celsius",
+        "gauge",
+        "Temperatures from hwmon and thermal zones.",
+    );
+    for t in &sample.temps {
+        let _ = writeln!(
+            out,
+            "asus_temperature_celsius{{source=\"{}\",sensor=\"{}\"}} {:.1}",
+            label(&t.source),
+            label(&t.label),
+            t.celsius
+        );
+    }
+    if let Some(hottest) = sensors::hottest_cpu(&sample.temps) {
+        header(
+            &mut out,
+            "asus_cpu_temperature_celsius",
+            "gauge",
+            "The hottest CPU sensor.",
+        );
+        let _ = writeln!(out, "asus_cpu_temperature_celsius {:.1}", hottest.celsius);
+    }
+
+    header(
+        &mut out,
+        "asus_profile_rule_switches",
+        "counter",
+        "Profile switches made by app profile rules.",
+    );
+    let _ = writeln!(
+        out,
+        "asus_profile_rule_switches_total {}",
+        app_profiles::SWITCHES.load(Ordering::Relaxed)
+    );
+
+    header(
+        &mut out,
+        "asus_sysfs_write_errors",
+        "counter",
+        "Failed or mismatched sysfs writes.",
+    );
+    let _ = writeln!(
+        out,
+        "asus_sysfs_write_errors_total {}",
+        sysfs::WRITE_ERRORS.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+
+    #[test]
+    fn serves_exposition_over_http() {
+        crate::tests::setup();
+        let (mut client, server) = UnixStream::pair().unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        serve(server);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.contains("asus_platform_profile{profile=\"balanced\"} 1\n"));
+        assert!(body.ends_with("# EOF\n"));
+    }
+}
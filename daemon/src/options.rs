@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use asus_control_protocol::SOCKET_PATH;
+
+use crate::config::CONFIG_PATH;
+
+const USAGE: &str = "Usage: asus-control-daemon [options]
+
+Options:
+  --socket <path>       listen on <path> instead of /run/asus-control-daemon.sock;
+                        point clients at it with ASUS_CONTROL_SOCKET=<path>
+  --config <path>       read <path> instead of /etc/asus-control/daemon.toml
+  --foreground          stay attached to the terminal and log to stderr
+  --log-level <filter>  log filter such as debug or asus_control_daemon=trace
+                        (default: RUST_LOG, then info)
+  --sysfs-root <dir>    look up /sys paths under <dir>, e.g. a copy of another machine's sysfs
+  --simulate            run against a simulated ASUS laptop instead of this machine
+  --user <name>         drop from root to <name> after startup, keeping only
+                        CAP_DAC_OVERRIDE (default: asus-control-daemon,
+                        created by setup.sh)
+  --no-sandbox          stay root and skip the Landlock filesystem sandbox
+  --check-config        validate the config file and exit
+  --help                show this help";
+
+pub struct Options {
+    pub socket: PathBuf,
+    pub config: PathBuf,
+    pub foreground: bool,
+    pub log_level: Option<String>,
+    pub sysfs_root: Option<PathBuf>,
+    pub simulate: bool,
+    pub user: String,
+    pub no_sandbox: bool,
+    pub check_config: bool,
+}
+
+impl Options {
+    /// Parses the daemon's arguments, accepting both `--opt value` and `--opt=value`.
+    /// Prints the usage and exits for `--help`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            socket: PathBuf::from(SOCKET_PATH),
+            config: PathBuf::from(CONFIG_PATH),
+            foreground: false,
+            log_level: None,
+            sysfs_root: None,
+            simulate: false,
+            user: "asus-control-daemon".into(),
+            no_sandbox: false,
+            check_config: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} requires a value\n\n{}", name, USAGE))
+            };
+
+            match name.as_str() {
+                "--socket" => options.socket = value()?.into(),
+                "--config" => options.config = value()?.into(),
+                "--log-level" => options.log_level = Some(value()?),
+                "--sysfs-root" => options.sysfs_root = Some(value()?.into()),
+                "--user" => options.user = value()?,
+                "--foreground" | "--simulate" | "--no-sandbox" | "--check-config"
+                    if inline.is_some() =>
+                {
+                    return Err(format!("{} takes no value", name));
+                }
+                "--foreground" => options.foreground = true,
+                "--simulate" => options.simulate = true,
+                "--no-sandbox" => options.no_sandbox = true,
+                "--check-config" => options.check_config = true,
+                "--help" | "-h" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                other => return Err(format!("unknown option: {}\n\n{}", other, USAGE)),
+            }
+        }
+        if options.simulate && options.sysfs_root.is_some() {
+            return Err("--simulate and --sysfs-root cannot be combined".into());
+        }
+        Ok(options)
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use crate::backend;
+use crate::config::{self, ProfileSettings};
+use crate::cpu_tuning;
+use crate::profile::{PlatformProfile, get_fan_profile};
+use crate::sensors::read_trimmed;
+use crate::sysfs::{self, selected};
+
+const ASPM_POLICY: &str = "/sys/module/pcie_aspm/parameters/policy";
+const MEM_SLEEP: &str = "/sys/power/mem_sleep";
+pub const NMI_WATCHDOG: &str = "/proc/sys/kernel/nmi_watchdog";
+
+pub const ASPM_POLICIES: &[&str] = &["default", "performance", "powersave", "powersupersave"];
+pub const MEM_SLEEP_MODES: &[&str] = &["s2idle", "shallow", "deep"];
+
+/// Writes one of the options a `a [b] c` style node lists.
+fn write_choice(path: &str, value: &str) -> Result<(), String> {
+    let path = sysfs::path(path);
+    let raw = read_trimmed(&path).ok_or_else(|| format!("{} not found", path.display()))?;
+    let options: Vec<&str> = raw
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c| c == '[' || c == ']'))
+        .collect();
+    if !options.contains(&value) {
+        return Err(format!("{} is not one of {}", value, options.join(", ")));
+    }
+    sysfs::write_over_baseline(path, value)
+}
+
+/// Whichever of the ASPM, suspend and watchdog nodes exist.
+pub fn nodes() -> Vec<PathBuf> {
+    [ASPM_POLICY, MEM_SLEEP, NMI_WATCHDOG]
+        .into_iter()
+        .map(sysfs::path)
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Applies the ASPM, suspend and watchdog settings configured for `profile`. Like the cpufreq
+/// settings, ones it leaves out go back to what they were before the daemon changed them.
+pub fn apply(profile: PlatformProfile) -> Result<(), String> {
+    let default = ProfileSettings::default();
+    let settings = config::get().profile.get(&profile).unwrap_or(&default);
+    let mut errors = Vec::new();
+
+    let aspm = match &settings.pcie_aspm_policy {
+        Some(policy) => write_choice(ASPM_POLICY, policy),
+        None => sysfs::restore_baseline(sysfs::path(ASPM_POLICY)),
+    };
+    if let Err(e) = aspm {
+        errors.push(format!("failed to set PCIe ASPM policy: {}", e));
+    }
+    let mem_sleep = match &settings.mem_sleep {
+        Some(mode) => write_choice(MEM_SLEEP, mode),
+        None => sysfs::restore_baseline(sysfs::path(MEM_SLEEP)),
+    };
+    if let Err(e) = mem_sleep {
+        errors.push(format!("failed to set suspend mode: {}", e));
+    }
+    let watchdog = sysfs::path(NMI_WATCHDOG);
+    let watchdog = match settings.nmi_watchdog {
+        Some(on) => sysfs::write_over_baseline(watchdog, if on { "1" } else { "0" }),
+        None => sysfs::restore_baseline(watchdog),
+    };
+    if let Err(e) = watchdog {
+        errors.push(format!("failed to set NMI watchdog: {}", e));
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join("; ")),
+    }
+}
+
+fn current(path: &str) -> String {
+    read_trimmed(&sysfs::path(path))
+        .map(|raw| selected(&raw).to_string())
+        .unwrap_or_else(|| "unavailable".into())
+}
+
+/// The active profile, its backend, and the current value of every setting tied to profiles,
+/// with the configured value alongside where it differs.
+pub fn get_profile_details() -> Result<String, String> {
+    let name = get_fan_profile()?;
+    let backend = backend::get().profile_interface().unwrap_or("none");
+    let default = ProfileSettings::default();
+    let settings = PlatformProfile::parse(&name)
+        .and_then(|p| config::get().profile.get(&p))
+        .unwrap_or(&default);
+
+    let on_off = |b: bool| if b { "on" } else { "off" }.to_string();
+    let line = |key: &str, current: String, wanted: Option<String>| match wanted {
+        Some(w) if w != current => format!("{}: {} (configured: {})", key, current, w),
+        _ => format!("{}: {}", key, current),
+    };
+
+    let watchdog = match read_trimmed(&sysfs::path(NMI_WATCHDOG)).as_deref() {
+        Some("0") => "off".into(),
+        Some(_) => "on".into(),
+        None => "unavailable".into(),
+    };
+
+    let mut lines = vec![
+        format!("profile: {}", name),
+        format!("backend: {}", backend),
+    ];
+    lines.extend(cpu_tuning::get_cpu_tuning().lines().map(String::from));
+    lines.push(line(
+        "pcie-aspm-policy",
+        current(ASPM_POLICY),
+        settings.pcie_aspm_policy.clone(),
+    ));
+    lines.push(line(
+        "mem-sleep",
+        current(MEM_SLEEP),
+        settings.mem_sleep.clone(),
+    ));
+    lines.push(line(
+        "nmi-watchdog",
+        watchdog,
+        settings.nmi_watchdog.map(on_off),
+    ));
+    Ok(lines.join("\n"))
+}
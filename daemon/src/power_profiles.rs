@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+use zbus::blocking::{Connection, fdo::DBusProxy};
+use zbus::fdo;
+use zbus::message::Header;
+use zbus::zvariant::{OwnedValue, Str};
+
+use crate::backend;
+use crate::dbus;
+use crate::profile::{PlatformProfile, get_fan_profile, set_fan_mode};
+use crate::transaction;
+
+pub const BUS_NAMES: &[&str] = &[
+    "net.hadess.PowerProfiles",
+    "org.freedesktop.UPower.PowerProfiles",
+];
+const HADESS_PATH: &str = "/net/hadess/PowerProfiles";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower/PowerProfiles";
+
+/// How often the active profile is checked for changes made through the socket or by rules.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// power-profiles-daemon's profile names and the platform profiles they map onto.
+const PROFILES: &[(&str, PlatformProfile)] = &[
+    ("power-saver", PlatformProfile::Quiet),
+    ("balanced", PlatformProfile::Balanced),
+    ("performance", PlatformProfile::Performance),
+];
+
+fn ppd_name(profile: PlatformProfile) -> &'static str {
+    PROFILES.iter().find(|(_, p)| *p == profile).unwrap().0
+}
+
+fn parse(name: &str) -> fdo::Result<PlatformProfile> {
+    PROFILES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, p)| *p)
+        .ok_or_else(|| fdo::Error::InvalidArgs(format!("Invalid profile name '{}'", name)))
+}
+
+fn dict(entries: &[(&str, &str)]) -> HashMap<String, OwnedValue> {
+    entries
+        .iter()
+        .map(|(k, v)| (k.to_string(), OwnedValue::from(Str::from(v.to_string()))))
+        .collect()
+}
+
+/// A `HoldProfile` request, kept until released or until its holder leaves the bus.
+struct Hold {
+    cookie: u32,
+    profile: PlatformProfile,
+    reason: String,
+    application_id: String,
+    holder: String,
+}
+
+struct Holds {
+    holds: Vec<Hold>,
+    next_cookie: u32,
+    /// The profile to go back to once the last hold is released.
+    restore: Option<PlatformProfile>,
+    /// Cookies cancelled by a manual profile change, still to be announced.
+    released: Vec<u32>,
+}
+
+static HOLDS: Mutex<Holds> = Mutex::new(Holds {
+    holds: Vec::new(),
+    next_cookie: 1,
+    restore: None,
+    released: Vec::new(),
+});
+
+/// Wakes the notifier so property changes go out without waiting for the next poll.
+static CHANGED: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+fn changed() {
+    if let Some(tx) = CHANGED.lock().unwrap().as_ref() {
+        let _ = tx.send(());
+    }
+}
+
+fn set_profile(profile: PlatformProfile) -> fdo::Result<()> {
+    set_fan_mode(profile)
+        .map(|_| ())
+        .map_err(fdo::Error::Failed)
+}
+
+/// Applies what the remaining holds ask for: power-saver wins over performance, and with no
+/// holds left the profile from before the first hold comes back.
+fn apply_holds(holds: &mut Holds) -> fdo::Result<()> {
+    let wanted = [PlatformProfile::Quiet, PlatformProfile::Performance]
+        .into_iter()
+        .find(|p| holds.holds.iter().any(|h| h.profile == *p));
+    match wanted.or_else(|| holds.restore.take()) {
+        Some(profile) => set_profile(profile),
+        None => Ok(()),
+    }
+}
+
+fn active_profile() -> fdo::Result<String> {
+    let name = get_fan_profile().map_err(fdo::Error::Failed)?;
+    Ok(PlatformProfile::parse(&name)
+        .map_or("balanced", ppd_name)
+        .into())
+}
+
+/// Choosing a profile by hand cancels every hold, as in power-profiles-daemon.
+fn set_active_profile(name: &str) -> fdo::Result<()> {
+    let _settings = transaction::lock();
+    let profile = parse(name)?;
+    {
+        let mut holds = HOLDS.lock().unwrap();
+        let cancelled = std::mem::take(&mut holds.holds);
+        holds.released.extend(cancelled.iter().map(|h| h.cookie));
+        holds.restore = None;
+    }
+    let result = set_profile(profile);
+    changed();
+    result
+}
+
+fn profiles() -> Vec<HashMap<String, OwnedValue>> {
+    let driver = backend::get().profile_interface().unwrap_or("none");
+    backend::get()
+        .profile_choices()
+        .into_iter()
+        .map(|p| {
+            dict(&[
+                ("Profile", ppd_name(p)),
+                ("Driver", driver),
+                ("PlatformDriver", driver),
+            ])
+        })
+        .collect()
+}
+
+fn active_profile_holds() -> Vec<HashMap<String, OwnedValue>> {
+    HOLDS
+        .lock()
+        .unwrap()
+        .holds
+        .iter()
+        .map(|h| {
+            dict(&[
+                ("ApplicationId", &h.application_id),
+                ("Profile", ppd_name(h.profile)),
+                ("Reason", &h.reason),
+            ])
+        })
+        .collect()
+}
+
+fn hold_profile(
+    profile: &str,
+    reason: String,
+    application_id: String,
+    holder: String,
+) -> fdo::Result<u32> {
+    let _settings = transaction::lock();
+    let held = parse(profile)?;
+    if held == PlatformProfile::Balanced {
+        return Err(fdo::Error::InvalidArgs(
+            "Only profiles 'performance' and 'power-saver' can be a hold".into(),
+        ));
+    }
+
+    let mut holds = HOLDS.lock().unwrap();
+    let first = holds.holds.is_empty();
+    if first {
+        holds.restore = get_fan_profile()
+            .ok()
+            .and_then(|n| PlatformProfile::parse(&n));
+    }
+    let cookie = holds.next_cookie;
+    holds.next_cookie += 1;
+    holds.holds.push(Hold {
+        cookie,
+        profile: held,
+        reason: reason.clone(),
+        application_id: application_id.clone(),
+        holder,
+    });
+    // A hold that couldn't be applied is dropped, as the caller never gets its cookie.
+    if let Err(e) = apply_holds(&mut holds) {
+        holds.holds.pop();
+        if first {
+            holds.restore = None;
+        }
+        return Err(e);
+    }
+    info!("{} holds {}: {}", application_id, profile, reason);
+    changed();
+    Ok(cookie)
+}
+
+fn release_profile(cookie: u32) -> fdo::Result<()> {
+    let _settings = transaction::lock();
+    let mut holds = HOLDS.lock().unwrap();
+    let Some(i) = holds.holds.iter().position(|h| h.cookie == cookie) else {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "No hold with cookie {}",
+            cookie
+        )));
+    };
+    holds.holds.remove(i);
+    let result = apply_holds(&mut holds);
+    changed();
+    result
+}
+
+/// Drops every hold owned by `holder`, which has left the bus.
+fn release_holder(holder: &str) {
+    let _settings = transaction::lock();
+    let mut holds = HOLDS.lock().unwrap();
+    let before = holds.holds.len();
+    holds.holds.retain(|h| h.holder != holder);
+    if holds.holds.len() == before {
+        return;
+    }
+    if let Err(e) = apply_holds(&mut holds) {
+        warn!("Failed to restore the profile after {} left: {}", holder, e);
+    }
+    changed();
+}
+
+/// power-profiles-daemon serves the same interface under its old and its UPower name.
+macro_rules! power_profiles_interface {
+    ($ty:ident, $name:literal, $path:ident) => {
+        struct $ty;
+
+        #[zbus::interface(name = $name)]
+        impl $ty {
+            #[zbus(property)]
+            fn active_profile(&self) -> fdo::Result<String> {
+                active_profile()
+            }
+
+            #[zbus(property)]
+            async fn set_active_profile(
+                &mut self,
+                profile: String,
+                #[zbus(connection)] conn: &zbus::Connection,
+                #[zbus(header)] header: Option<Header<'_>>,
+            ) -> fdo::Result<()> {
+                dbus::authorize(conn, header.as_ref()).await?;
+                set_active_profile(&profile)
+            }
+
+            #[zbus(property)]
+            fn performance_inhibited(&self) -> String {
+                String::new()
+            }
+
+            #[zbus(property)]
+            fn performance_degraded(&self) -> String {
+                String::new()
+            }
+
+            #[zbus(property)]
+            fn profiles(&self) -> Vec<HashMap<String, OwnedValue>> {
+                profiles()
+            }
+
+            #[zbus(property)]
+            fn actions(&self) -> Vec<String> {
+                Vec::new()
+            }
+
+            #[zbus(property)]
+            fn active_profile_holds(&self) -> Vec<HashMap<String, OwnedValue>> {
+                active_profile_holds()
+            }
+
+            #[zbus(property)]
+            fn version(&self) -> String {
+                env!("CARGO_PKG_VERSION").into()
+            }
+
+            async fn hold_profile(
+                &self,
+                profile: String,
+                reason: String,
+                application_id: String,
+                #[zbus(connection)] conn: &zbus::Connection,
+                #[zbus(header)] header: Header<'_>,
+            ) -> fdo::Result<u32> {
+                dbus::authorize(conn, Some(&header)).await?;
+                let holder = header.sender().map(|s| s.to_string()).unwrap_or_default();
+                hold_profile(&profile, reason, application_id, holder)
+            }
+
+            async fn release_profile(
+                &self,
+                cookie: u32,
+                #[zbus(connection)] conn: &zbus::Connection,
+                #[zbus(header)] header: Header<'_>,
+            ) -> fdo::Result<()> {
+                dbus::authorize(conn, Some(&header)).await?;
+                release_profile(cookie)
+            }
+
+            #[zbus(signal)]
+            async fn profile_released(
+                emitter: &zbus::object_server::SignalEmitter<'_>,
+                cookie: u32,
+            ) -> zbus::Result<()>;
+        }
+
+        impl $ty {
+            /// Announces the current profile and holds, and any cancelled holds.
+            fn emit_changes(conn: &Connection, released: &[u32]) -> zbus::Result<()> {
+                let iface = conn.object_server().interface::<_, $ty>($path)?;
+                let emitter = iface.signal_emitter();
+                let object = iface.get();
+                zbus::block_on(async {
+                    object.active_profile_changed(emitter).await?;
+                    object.active_profile_holds_changed(emitter).await?;
+                    for cookie in released {
+                        $ty::profile_released(emitter, *cookie).await?;
+                    }
+                    Ok(())
+                })
+            }
+        }
+    };
+}
+
+power_profiles_interface!(Hadess, "net.hadess.PowerProfiles", HADESS_PATH);
+power_profiles_interface!(UPower, "org.freedesktop.UPower.PowerProfiles", UPOWER_PATH);
+
+/// Sends property changes when woken, or when a poll finds the profile changed elsewhere.
+fn notify(conn: Connection, rx: Receiver<()>) {
+    let mut last = active_profile().ok();
+    loop {
+        let woken = rx.recv_timeout(POLL_INTERVAL).is_ok();
+        let active = active_profile().ok();
+        if !woken && active == last {
+            continue;
+        }
+        last = active;
+
+        let released = std::mem::take(&mut HOLDS.lock().unwrap().released);
+        let result = Hadess::emit_changes(&conn, &released)
+            .and_then(|_| UPower::emit_changes(&conn, &released));
+        if let Err(e) = result {
+            warn!("Failed to announce profile change: {}", e);
+        }
+    }
+}
+
+/// Serves both interfaces on `conn` and keeps their properties and holds up to date.
+pub fn serve(conn: &Connection) -> zbus::Result<()> {
+    conn.object_server().at(HADESS_PATH, Hadess)?;
+    conn.object_server().at(UPOWER_PATH, UPower)?;
+
+    let (tx, rx) = mpsc::channel();
+    *CHANGED.lock().unwrap() = Some(tx);
+    let notifier = conn.clone();
+    thread::spawn(move || notify(notifier, rx));
+
+    // Holders leaving are only reported by a bus; a peer's holds last until released.
+    if !conn.is_bus() {
+        return Ok(());
+    }
+    let owners = DBusProxy::new(conn)?.receive_name_owner_changed()?;
+    thread::spawn(move || {
+        for signal in owners {
+            if let Ok(args) = signal.args()
+                && args.new_owner().is_none()
+            {
+                release_holder(args.name());
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use zbus::blocking::{MessageIterator, connection};
+    use zbus::message::Type;
+    use zbus::zvariant::Value;
+
+    use super::*;
+    use crate::backend::Memory;
+
+    const IFACE: &str = "net.hadess.PowerProfiles";
+
+    // `unix_stream` is only deprecated for builds with zbus' tokio feature, which this isn't.
+    #[allow(deprecated)]
+    #[test]
+    fn holds_and_releases_profiles_over_a_peer_connection() {
+        crate::tests::setup();
+        backend::set_shared(Box::new(Memory::new()));
+        let (server, client) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            // Serving the objects while building has them answer from the first message on,
+            // which `serve` adding them to a running connection doesn't guarantee.
+            connection::Builder::unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(HADESS_PATH, Hadess)
+                .unwrap()
+                .serve_at(UPOWER_PATH, UPower)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = connection::Builder::unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+        let server = server.join().unwrap();
+        serve(&server).unwrap();
+        let mut signals = MessageIterator::from(&client);
+
+        let active = || -> String {
+            let reply = client
+                .call_method(
+                    None::<()>,
+                    HADESS_PATH,
+                    Some("org.freedesktop.DBus.Properties"),
+                    "Get",
+                    &(IFACE, "ActiveProfile"),
+                )
+                .unwrap();
+            let value: zbus::zvariant::OwnedValue = reply.body().deserialize().unwrap();
+            String::try_from(value).unwrap()
+        };
+        let set_active = |name: &str| {
+            client
+                .call_method(
+                    None::<()>,
+                    HADESS_PATH,
+                    Some("org.freedesktop.DBus.Properties"),
+                    "Set",
+                    &(IFACE, "ActiveProfile", Value::from(name)),
+                )
+                .unwrap();
+        };
+        let hold = |profile: &str| -> u32 {
+            client
+                .call_method(
+                    None::<()>,
+                    HADESS_PATH,
+                    Some(IFACE),
+                    "HoldProfile",
+                    &(profile, "testing", "asus-control-test"),
+                )
+                .unwrap()
+                .body()
+                .deserialize()
+                .unwrap()
+        };
+
+        set_active("balanced");
+        assert_eq!(active(), "balanced");
+
+        let cookie = hold("performance");
+        assert_eq!(active(), "performance");
+        client
+            .call_method(
+                None::<()>,
+                HADESS_PATH,
+                Some(IFACE),
+                "ReleaseProfile",
+                &cookie,
+            )
+            .unwrap();
+        assert_eq!(active(), "balanced");
+
+        // Picking a profile by hand cancels the hold and tells its holder.
+        let cookie = hold("performance");
+        set_active("power-saver");
+        assert_eq!(active(), "power-saver");
+        let released = signals.by_ref().filter_map(Result::ok).find(|m| {
+            m.message_type() == Type::Signal
+                && m.header().member().is_some_and(|n| n == "ProfileReleased")
+        });
+        assert_eq!(
+            released.unwrap().body().deserialize::<u32>().unwrap(),
+            cookie
+        );
+    }
+}
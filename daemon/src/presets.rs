@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use toml::{Table, Value};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::fan_curve::{Curve, Fan};
+use crate::get_battery_threshold;
+use crate::keyboard;
+use crate::profile::{PlatformProfile, get_fan_profile};
+use crate::sysfs;
+use crate::transaction;
+
+/// `set` targets a preset may contain, in the order they are usually applied.
+pub const SETTINGS: &[&str] = &[
+    "profile",
+    "fan-curve",
+    "battery-threshold",
+    "keyboard-backlight",
+    "firmware-attr",
+];
+
+/// Preset names mapped to tables of `set` targets and values.
+type Presets = Table;
+
+/// Flattens a preset table into `(target, value)` pairs, keeping file order.
+/// Array values yield one pair per element, e.g. several firmware attributes.
+pub fn entries(preset: &Value) -> Result<Vec<(String, String)>, String> {
+    let table = preset
+        .as_table()
+        .ok_or_else(|| "a preset must be a table".to_string())?;
+    let mut entries = Vec::new();
+    for (target, value) in table {
+        if !SETTINGS.contains(&target.as_str()) {
+            return Err(format!("unknown preset setting: {}", target));
+        }
+        let values = match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        };
+        for v in values {
+            let v = match v {
+                Value::String(s) => s.clone(),
+                Value::Integer(n) => n.to_string(),
+                _ => return Err(format!("{} must be a string or an integer", target)),
+            };
+            check(target, &v).map_err(|e| format!("{}: {}", target, e))?;
+            entries.push((target.clone(), v));
+        }
+    }
+    Ok(entries)
+}
+
+/// Rejects values `set` can never take, whatever the hardware, so they fail when the preset is
+/// read rather than when it's applied.
+fn check(target: &str, value: &str) -> Result<(), String> {
+    let mut words = value.split_whitespace();
+    match target {
+        "profile" => PlatformProfile::parse(value)
+            .map(|_| ())
+            .ok_or_else(|| format!("invalid profile: {}", value)),
+        "battery-threshold" => match value.parse::<i32>() {
+            Ok(n) if (0..=100).contains(&n) => Ok(()),
+            _ => Err(format!("threshold out of range (0-100): {}", value)),
+        },
+        "keyboard-backlight" => value
+            .parse::<u32>()
+            .map(|_| ())
+            .map_err(|_| format!("invalid keyboard backlight level: {}", value)),
+        "fan-curve" => {
+            let (Some(fan), Some(curve)) = (words.next(), words.next()) else {
+                return Err("expected a fan and a curve".into());
+            };
+            Fan::parse(fan).ok_or_else(|| format!("invalid fan: {}", fan))?;
+            Curve::parse(curve)?;
+            match words.next() {
+                Some(p) if PlatformProfile::parse(p).is_none() => {
+                    Err(format!("invalid profile: {}", p))
+                }
+                _ => Ok(()),
+            }
+        }
+        "firmware-attr" => match words.nth(1) {
+            Some(_) => Ok(()),
+            None => Err("expected a name and a value".into()),
+        },
+        _ => Ok(()),
+    }
+}
+
+fn state_path() -> PathBuf {
+    config::state_dir().join("presets.toml")
+}
+
+fn load_saved() -> Result<Presets, String> {
+    sysfs::read_state(state_path())
+}
+
+fn store_saved(presets: &Presets) -> Result<(), String> {
+    let text = toml::to_string(presets).map_err(|e| e.to_string())?;
+    sysfs::write_state(state_path(), &text)
+}
+
+/// Presets from the config file, then saved ones, which win on a name clash.
+fn all() -> Result<Presets, String> {
+    let mut presets: Presets = config::get()
+        .preset
+        .iter()
+        .map(|(name, preset)| (name.clone(), preset.0.clone()))
+        .collect();
+    presets.extend(load_saved()?);
+    Ok(presets)
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c == '=') {
+        return Err(format!("invalid preset name: {}", name));
+    }
+    Ok(())
+}
+
+pub fn get_presets() -> Result<String, String> {
+    let presets = all()?;
+    if presets.is_empty() {
+        return Ok("none".into());
+    }
+
+    Ok(presets
+        .iter()
+        .map(|(name, preset)| {
+            let settings = match entries(preset) {
+                Ok(e) => e
+                    .iter()
+                    .map(|(t, v)| match v.contains(' ') {
+                        true => format!("{}=\"{}\"", t, v),
+                        false => format!("{}={}", t, v),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Err(e) => format!("invalid ({})", e),
+            };
+            format!("{}: {}", name, settings)
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+pub fn apply_preset(name: &str) -> Result<String, String> {
+    let presets = all()?;
+    let preset = presets
+        .get(name)
+        .ok_or_else(|| format!("unknown preset: {}", name))?;
+
+    let commands: Vec<String> = entries(preset)?
+        .iter()
+        .map(|(target, value)| format!("set {} {}", target, value))
+        .collect();
+    match transaction::run(&commands) {
+        (true, report) => Ok(format!("Preset {} applied\n{}", name, report)),
+        (false, report) => Err(format!(
+            "preset {} not applied: {}",
+            name,
+            report.strip_prefix("error: ").unwrap_or(&report)
+        )),
+    }
+}
+
+/// Applies the config file's `[defaults]`, if any, as one transaction.
+pub fn apply_defaults() {
+    let Some(defaults) = &config::get().defaults else {
+        return;
+    };
+    let commands: Vec<String> = match entries(&defaults.0) {
+        Ok(e) => e
+            .iter()
+            .map(|(target, value)| format!("set {} {}", target, value))
+            .collect(),
+        Err(e) => {
+            warn!("Invalid defaults: {}", e);
+            return;
+        }
+    };
+    match transaction::run(&commands) {
+        (true, report) => info!("Defaults applied\n{}", report),
+        (false, report) => warn!("Defaults not applied: {}", report),
+    }
+}
+
+/// Saves the current profile, charge threshold and keyboard backlight as a preset.
+pub fn save_preset(name: &str) -> Result<String, String> {
+    let _settings = transaction::lock();
+    validate_name(name)?;
+
+    let mut table = Table::new();
+    if let Ok(profile) = get_fan_profile() {
+        table.insert("profile".into(), Value::String(profile));
+    }
+    if let Some(n) = get_battery_threshold()
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+    {
+        table.insert("battery-threshold".into(), Value::Integer(n));
+    }
+    if let Some(n) = keyboard::get_keyboard_backlight()
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+    {
+        table.insert("keyboard-backlight".into(), Value::Integer(n));
+    }
+    if table.is_empty() {
+        return Err("no settings could be read".into());
+    }
+
+    let mut saved = load_saved()?;
+    saved.insert(name.to_string(), Value::Table(table));
+    store_saved(&saved)?;
+    Ok(format!("Preset {} saved", name))
+}
+
+pub fn delete_preset(name: &str) -> Result<String, String> {
+    let _settings = transaction::lock();
+    let mut saved = load_saved()?;
+    if saved.remove(name).is_none() {
+        if config::get().preset.contains_key(name) {
+            return Err(format!(
+                "preset {} is defined in {}",
+                name,
+                config::path().display()
+            ));
+        }
+        return Err(format!("unknown preset: {}", name));
+    }
+    store_saved(&saved)?;
+    Ok(format!("Preset {} deleted", name))
+}
@@ -0,0 +1,150 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::transaction;
+use crate::{backend, cpu_tuning, fan_curve, power_knobs, sysfs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlatformProfile {
+    Quiet,
+    Balanced,
+    Performance,
+}
+
+impl PlatformProfile {
+    pub fn parse(s: &str) -> Option<PlatformProfile> {
+        match s.trim() {
+            "quiet" => Some(PlatformProfile::Quiet),
+            "balanced" => Some(PlatformProfile::Balanced),
+            "performance" => Some(PlatformProfile::Performance),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlatformProfile::Quiet => "quiet",
+            PlatformProfile::Balanced => "balanced",
+            PlatformProfile::Performance => "performance",
+        }
+    }
+}
+
+const CHOICES_PATH: &str = "/sys/firmware/acpi/platform_profile_choices";
+
+/// The kernel interface used to switch profiles, in order of preference.
+#[derive(Debug, Clone, Copy)]
+pub enum ProfileBackend {
+    PlatformProfile,
+    ThrottleThermalPolicy,
+    FanBoostMode,
+}
+
+impl ProfileBackend {
+    const ALL: [ProfileBackend; 3] = [
+        ProfileBackend::PlatformProfile,
+        ProfileBackend::ThrottleThermalPolicy,
+        ProfileBackend::FanBoostMode,
+    ];
+
+    pub fn detect() -> Option<ProfileBackend> {
+        Self::ALL.into_iter().find(|b| b.path().exists())
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProfileBackend::PlatformProfile => "platform_profile",
+            ProfileBackend::ThrottleThermalPolicy => "throttle_thermal_policy",
+            ProfileBackend::FanBoostMode => "fan_boost_mode",
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        sysfs::path(match self {
+            ProfileBackend::PlatformProfile => "/sys/firmware/acpi/platform_profile",
+            ProfileBackend::ThrottleThermalPolicy => {
+                "/sys/devices/platform/asus-nb-wmi/throttle_thermal_policy"
+            }
+            ProfileBackend::FanBoostMode => "/sys/devices/platform/asus-nb-wmi/fan_boost_mode",
+        })
+    }
+
+    /// The supported profiles this backend offers.
+    pub fn choices(&self) -> Vec<PlatformProfile> {
+        let all = vec![
+            PlatformProfile::Quiet,
+            PlatformProfile::Balanced,
+            PlatformProfile::Performance,
+        ];
+        match self {
+            ProfileBackend::PlatformProfile => {
+                let offered =
+                    std::fs::read_to_string(sysfs::path(CHOICES_PATH)).unwrap_or_default();
+                let offered: Vec<&str> = offered.split_whitespace().collect();
+                all.into_iter()
+                    .filter(|p| offered.contains(&p.as_str()))
+                    .collect()
+            }
+            _ => all,
+        }
+    }
+
+    /// asus-wmi numbers its modes 0 = normal, 1 = overboost, 2 = silent.
+    pub fn encode(&self, profile: PlatformProfile) -> &'static str {
+        match (self, profile) {
+            (ProfileBackend::PlatformProfile, p) => p.as_str(),
+            (_, PlatformProfile::Balanced) => "0",
+            (_, PlatformProfile::Performance) => "1",
+            (_, PlatformProfile::Quiet) => "2",
+        }
+    }
+
+    pub fn decode(&self, raw: &str) -> String {
+        let profile = match (self, raw) {
+            (ProfileBackend::PlatformProfile, _) => None,
+            (_, "0") => Some(PlatformProfile::Balanced),
+            (_, "1") => Some(PlatformProfile::Performance),
+            (_, "2") => Some(PlatformProfile::Quiet),
+            _ => None,
+        };
+        profile.map_or_else(|| raw.to_string(), |p| p.as_str().to_string())
+    }
+}
+
+/// Returns the active profile name, whichever interface provides it.
+pub fn get_fan_profile() -> Result<String, String> {
+    backend::get().profile()
+}
+
+fn interface() -> Result<&'static str, String> {
+    backend::get()
+        .profile_interface()
+        .ok_or_else(|| "no platform profile interface found".into())
+}
+
+/// Switches the profile, then re-applies the fan curves, CPU tuning and power knobs tied to it.
+/// A setting that fails or doesn't stick fails the switch, after the others were still tried.
+pub fn set_fan_mode(profile: PlatformProfile) -> Result<String, String> {
+    let _settings = transaction::lock();
+    let interface = interface()?;
+    backend::get().set_profile(profile)?;
+    fan_curve::reapply(profile);
+    let errors: Vec<String> = [cpu_tuning::apply(profile), power_knobs::apply(profile)]
+        .into_iter()
+        .filter_map(Result::err)
+        .collect();
+    if !errors.is_empty() {
+        return Err(format!(
+            "profile set to {}, but {}",
+            profile.as_str(),
+            errors.join("; ")
+        ));
+    }
+    Ok(format!(
+        "Profile set to {} ({})",
+        profile.as_str(),
+        interface
+    ))
+}
@@ -0,0 +1,219 @@
+use std::fs;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::sysfs;
+
+const DMI_DIR: &str = "/sys/class/dmi/id";
+const QUIRKS_DIR: &str = "/etc/asus-control/quirks.d";
+const BUILTIN: &str = include_str!("../quirks.toml");
+
+#[derive(Debug, Default)]
+pub struct Dmi {
+    pub sys_vendor: String,
+    pub product_name: String,
+    pub board_name: String,
+    pub bios_version: String,
+}
+
+impl Dmi {
+    fn read() -> Dmi {
+        let read = |field: &str| {
+            fs::read_to_string(sysfs::path(DMI_DIR).join(field))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        Dmi {
+            sys_vendor: read("sys_vendor"),
+            product_name: read("product_name"),
+            board_name: read("board_name"),
+            bios_version: read("bios_version"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct QuirkEntry {
+    name: String,
+    sys_vendor: Option<String>,
+    product_name: Option<String>,
+    board_name: Option<String>,
+    bios_version: Option<String>,
+    /// The only charge thresholds the EC honours.
+    threshold_values: Option<Vec<i32>>,
+    /// power_supply name of the battery with charge_control_end_threshold.
+    battery: Option<String>,
+    /// Factor applied to fan RPM readings from the asus hwmon.
+    fan_rpm_multiplier: Option<u32>,
+}
+
+impl QuirkEntry {
+    fn matches(&self, dmi: &Dmi) -> bool {
+        [
+            (&self.sys_vendor, &dmi.sys_vendor),
+            (&self.product_name, &dmi.product_name),
+            (&self.board_name, &dmi.board_name),
+            (&self.bios_version, &dmi.bios_version),
+        ]
+        .iter()
+        .all(|(pattern, value)| pattern.as_ref().is_none_or(|p| glob_match(p, value)))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuirkFile {
+    #[serde(default)]
+    quirk: Vec<QuirkEntry>,
+}
+
+/// The machine's DMI identity and the combined effect of all matching quirks.
+#[derive(Debug, Default)]
+pub struct Quirks {
+    pub dmi: Dmi,
+    pub matched: Vec<String>,
+    pub threshold_values: Option<Vec<i32>>,
+    pub battery: Option<String>,
+    pub fan_rpm_multiplier: Option<u32>,
+}
+
+static QUIRKS: OnceLock<Quirks> = OnceLock::new();
+
+/// Reads DMI and matches it against the built-in table, then quirks.d in file name order.
+/// Later entries override earlier ones.
+pub fn init() {
+    let dmi = Dmi::read();
+    let mut entries = parse(BUILTIN, "built-in quirks");
+
+    if let Ok(dir) = fs::read_dir(QUIRKS_DIR) {
+        let mut files: Vec<_> = dir
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+            .collect();
+        files.sort();
+        for file in files {
+            match fs::read_to_string(&file) {
+                Ok(text) => entries.extend(parse(&text, &file.display().to_string())),
+                Err(e) => warn!("Failed to read {}: {}", file.display(), e),
+            }
+        }
+    }
+
+    let mut quirks = Quirks::default();
+    for entry in entries.into_iter().filter(|e| e.matches(&dmi)) {
+        quirks.matched.push(entry.name);
+        if entry.threshold_values.is_some() {
+            quirks.threshold_values = entry.threshold_values;
+        }
+        if entry.battery.is_some() {
+            quirks.battery = entry.battery;
+        }
+        if entry.fan_rpm_multiplier.is_some() {
+            quirks.fan_rpm_multiplier = entry.fan_rpm_multiplier;
+        }
+    }
+    quirks.dmi = dmi;
+
+    if !quirks.matched.is_empty() {
+        info!("Applying quirks: {}", quirks.matched.join(", "));
+    }
+    let _ = QUIRKS.set(quirks);
+}
+
+fn parse(text: &str, origin: &str) -> Vec<QuirkEntry> {
+    match toml::from_str::<QuirkFile>(text) {
+        Ok(f) => f.quirk,
+        Err(e) => {
+            warn!("Ignoring invalid {}: {}", origin, e);
+            Vec::new()
+        }
+    }
+}
+
+pub fn get() -> &'static Quirks {
+    QUIRKS.get_or_init(Quirks::default)
+}
+
+/// Matches `value` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+pub fn get_system_info() -> String {
+    let q = get();
+    let mut lines = vec![
+        format!("sys_vendor: {}", q.dmi.sys_vendor),
+        format!("product_name: {}", q.dmi.product_name),
+        format!("board_name: {}", q.dmi.board_name),
+        format!("bios_version: {}", q.dmi.bios_version),
+    ];
+    if q.matched.is_empty() {
+        lines.push("quirks: none".into());
+    } else {
+        lines.push(format!("quirks: {}", q.matched.join(", ")));
+    }
+    if let Some(values) = &q.threshold_values {
+        let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        lines.push(format!("threshold-values: {}", values.join(",")));
+    }
+    if let Some(battery) = &q.battery {
+        lines.push(format!("battery: {}", battery));
+    }
+    if let Some(m) = q.fan_rpm_multiplier {
+        lines.push(format!("fan-rpm-multiplier: {}", m));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        let cases = [
+            ("ROG Strix G513QM", "ROG Strix G513QM", true),
+            ("ROG Strix G513QM", "ROG Strix G513QMX", false),
+            ("ROG*", "ROG Zephyrus G14", true),
+            ("ROG*", "TUF Gaming", false),
+            ("*G14", "ROG Zephyrus G14", true),
+            ("*G14", "ROG Zephyrus G14 GA401", false),
+            ("*Zephyrus*", "ROG Zephyrus G14", true),
+            ("ROG*G*14", "ROG Zephyrus G14", true),
+            ("ROG*GA*14", "ROG Zephyrus G14", false),
+            ("ab*ba", "aba", false),
+            ("*", "", true),
+            ("", "", true),
+            ("", "x", false),
+        ];
+        for (pattern, value, expected) in cases {
+            assert_eq!(
+                glob_match(pattern, value),
+                expected,
+                "{:?} against {:?}",
+                pattern,
+                value
+            );
+        }
+    }
+}
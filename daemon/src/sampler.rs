@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::backend;
+use crate::config::SamplerConfig;
+use crate::sensors::{self, FanReading, TempReading};
+
+/// How long a subscriber may stall a write before it is dropped.
+const SUBSCRIBER_TIMEOUT: Duration = Duration::from_secs(1);
+/// Subscribers beyond this many are turned away, which also bounds how long one round of
+/// writes can take.
+const MAX_SUBSCRIBERS: usize = 16;
+
+/// One reading of every sensor the sampler watches.
+pub struct Sample {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub fans: Vec<FanReading>,
+    pub temps: Vec<TempReading>,
+    /// Battery discharge (or charge) rate in watts.
+    pub battery_power: Option<f64>,
+}
+
+impl Sample {
+    fn take() -> Sample {
+        Sample {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            fans: backend::get().fans(),
+            temps: sensors::temperatures(),
+            battery_power: backend::get().battery().power,
+        }
+    }
+
+    /// `key: value` lines using the same keys as `get status`.
+    fn render(&self) -> String {
+        let mut lines = vec![format!("time: {}", self.time)];
+        for fan in &self.fans {
+            lines.push(format!("fan.{}: {}", fan.label, fan.rpm));
+        }
+        if let Some(hottest) = sensors::hottest_cpu(&self.temps) {
+            lines.push(format!("cpu-temp: {:.1}", hottest.celsius));
+        }
+        for t in &self.temps {
+            lines.push(format!("temp.{}/{}: {:.1}", t.source, t.label, t.celsius));
+        }
+        lines.push(format!(
+            "battery-power: {}",
+            self.battery_power
+                .map_or_else(|| "unavailable".into(), |w| format!("{:.1}", w))
+        ));
+        lines.join("\n")
+    }
+
+    /// A single line for the history: time, hottest CPU, battery power and each fan.
+    fn summary(&self) -> String {
+        let mut fields = vec![self.time.to_string()];
+        if let Some(hottest) = sensors::hottest_cpu(&self.temps) {
+            fields.push(format!("cpu-temp={:.1}", hottest.celsius));
+        }
+        if let Some(w) = self.battery_power {
+            fields.push(format!("battery-power={:.1}", w));
+        }
+        for fan in &self.fans {
+            fields.push(format!("fan.{}={}", fan.label, fan.rpm));
+        }
+        fields.join(" ")
+    }
+}
+
+static HISTORY: Mutex<VecDeque<Arc<Sample>>> = Mutex::new(VecDeque::new());
+static SUBSCRIBERS: Mutex<Vec<UnixStream>> = Mutex::new(Vec::new());
+
+pub fn spawn(config: &'static SamplerConfig) {
+    let interval = Duration::from_secs(config.interval.max(1));
+    let history = config.history.max(1);
+    // Subscribers are written from their own thread so slow ones never delay sampling. A
+    // sample arriving while the last is still being sent is skipped.
+    let (tx, rx) = mpsc::sync_channel::<Arc<Sample>>(1);
+    thread::spawn(move || {
+        for sample in rx {
+            publish(&sample);
+        }
+    });
+    thread::spawn(move || {
+        loop {
+            let sample = Arc::new(Sample::take());
+            {
+                let mut samples = HISTORY.lock().unwrap();
+                samples.push_back(sample.clone());
+                while samples.len() > history {
+                    samples.pop_front();
+                }
+            }
+            let _ = tx.try_send(sample);
+            thread::sleep(interval);
+        }
+    });
+}
+
+/// The most recent sample, or a fresh one if the sampler hasn't produced any yet.
+pub fn latest() -> Arc<Sample> {
+    if let Some(sample) = HISTORY.lock().unwrap().back() {
+        return sample.clone();
+    }
+    Arc::new(Sample::take())
+}
+
+/// Sends each subscriber the sample followed by an empty line, dropping those that fail.
+fn publish(sample: &Sample) {
+    let message = format!("{}\n\n", sample.render());
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain_mut(|stream| stream.write_all(message.as_bytes()).is_ok());
+}
+
+/// Keeps `stream` open and pushes every new sample to it, starting with the latest one.
+pub fn subscribe(mut stream: UnixStream) {
+    if stream.set_write_timeout(Some(SUBSCRIBER_TIMEOUT)).is_err() {
+        return;
+    }
+    if SUBSCRIBERS.lock().unwrap().len() >= MAX_SUBSCRIBERS {
+        let _ = stream.write_all(b"error: too many subscribers\n");
+        return;
+    }
+    let message = format!("{}\n\n", latest().render());
+    if stream.write_all(message.as_bytes()).is_ok() {
+        SUBSCRIBERS.lock().unwrap().push(stream);
+    }
+}
+
+/// One line per stored sample, oldest first.
+pub fn get_sensor_history() -> Result<String, String> {
+    let samples = HISTORY.lock().unwrap();
+    if samples.is_empty() {
+        return Err("no samples yet".into());
+    }
+    Ok(samples
+        .iter()
+        .map(|s| s.summary())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
@@ -0,0 +1,174 @@
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+
+use caps::{CapSet, Capability, CapsHashSet};
+use landlock::{
+    ABI, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus,
+    path_beneath_rules,
+};
+use tracing::{debug, info, warn};
+
+use crate::backend;
+use crate::config;
+use crate::cpu_tuning;
+use crate::fan_curve;
+use crate::firmware_attrs;
+use crate::power_knobs::{self, NMI_WATCHDOG};
+use crate::sysfs;
+
+/// The one capability kept after dropping root: writing root-owned sysfs nodes.
+const KEPT: Capability = Capability::CAP_DAC_OVERRIDE;
+
+/// Nodes that check the writer's uid instead of its capabilities, opened while still root.
+const HELD: &[&str] = &[NMI_WATCHDOG];
+
+/// Drops to `user` and confines the daemon to the files it needs. Must run before any other
+/// thread is started, as capabilities and the Landlock domain only apply to the calling
+/// thread and those it spawns later.
+pub fn enter(user: &str) -> Result<(), String> {
+    for node in HELD {
+        let path = sysfs::path(node);
+        if path.exists()
+            && let Err(e) = sysfs::hold(path)
+        {
+            warn!("{}", e);
+        }
+    }
+    // Created now so it can be granted below even if nothing was saved yet.
+    let _ = fs::create_dir_all(config::state_dir());
+
+    // SAFETY: geteuid has no preconditions.
+    match unsafe { libc::geteuid() } {
+        0 => drop_privileges(user)?,
+        uid => debug!("Running as uid {}, not dropping privileges", uid),
+    }
+    verify();
+    restrict()
+}
+
+fn drop_privileges(user: &str) -> Result<(), String> {
+    let name = CString::new(user).map_err(|_| format!("invalid user name: {}", user))?;
+    // SAFETY: the process is still single-threaded, so nothing else can reuse the static
+    // entry getpwnam returns before its fields are copied out.
+    let (uid, gid) = unsafe {
+        let pw = libc::getpwnam(name.as_ptr());
+        if pw.is_null() {
+            return Err(format!(
+                "unknown user: {} (setup.sh creates the default one)",
+                user
+            ));
+        }
+        ((*pw).pw_uid, (*pw).pw_gid)
+    };
+    if uid == 0 {
+        return Ok(());
+    }
+
+    let failed = |what: &str| format!("failed to {}: {}", what, std::io::Error::last_os_error());
+    // Only what this kernel knows: older ones reject newer capabilities such as CAP_BPF.
+    for cap in caps::runtime::thread_all_supported()
+        .into_iter()
+        .filter(|c| *c != KEPT)
+    {
+        caps::drop(None, CapSet::Bounding, cap)
+            .map_err(|e| format!("failed to drop {}: {}", cap, e))?;
+    }
+    // SAFETY: these only change this process's credentials; each result is checked.
+    unsafe {
+        if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) != 0 {
+            return Err(failed("keep capabilities"));
+        }
+        if libc::setgroups(0, std::ptr::null()) != 0 {
+            return Err(failed("clear supplementary groups"));
+        }
+        if libc::setresgid(gid, gid, gid) != 0 {
+            return Err(failed("set group id"));
+        }
+        if libc::setresuid(uid, uid, uid) != 0 {
+            return Err(failed("set user id"));
+        }
+    }
+
+    let kept = CapsHashSet::from([KEPT]);
+    caps::set(None, CapSet::Permitted, &kept)
+        .and_then(|_| caps::set(None, CapSet::Effective, &kept))
+        .and_then(|_| caps::clear(None, CapSet::Inheritable))
+        .map_err(|e| format!("failed to set capabilities: {}", e))?;
+    info!("Running as {} with only {}", user, KEPT);
+    Ok(())
+}
+
+/// Warns about nodes the daemon can no longer write.
+fn verify() {
+    let nodes = backend::get()
+        .nodes()
+        .into_iter()
+        .chain(fan_curve::nodes())
+        .chain(cpu_tuning::nodes())
+        .chain(power_knobs::nodes())
+        .chain(firmware_attrs::nodes());
+    for path in nodes {
+        if sysfs::is_held(&path) {
+            continue;
+        }
+        let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+            continue;
+        };
+        // SAFETY: c_path is a valid NUL-terminated string.
+        let writable = unsafe {
+            libc::faccessat(
+                libc::AT_FDCWD,
+                c_path.as_ptr(),
+                libc::W_OK,
+                libc::AT_EACCESS,
+            )
+        } == 0;
+        if !writable {
+            warn!(sysfs_path = %path.display(), "Not writable after dropping privileges");
+        }
+    }
+}
+
+/// Limits the filesystem to reading /proc and TLP's settings, writing the sysfs tree and
+/// `/proc/sys`, and managing the state directory; sockets are all bound by then. Kernels
+/// without Landlock leave the daemon unconfined, with a warning.
+fn restrict() -> Result<(), String> {
+    let abi = ABI::V3;
+    let read = AccessFs::from_read(abi);
+    let write = read | AccessFs::WriteFile | AccessFs::Truncate;
+
+    let existing = |paths: Vec<PathBuf>| paths.into_iter().filter(|p| p.exists()).collect();
+    let read_paths: Vec<PathBuf> = existing(vec![
+        "/proc".into(),
+        "/etc/tlp.conf".into(),
+        "/etc/tlp.d".into(),
+    ]);
+    let write_paths: Vec<PathBuf> = existing(vec![sysfs::path("/sys"), sysfs::path("/proc/sys")]);
+
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))
+        .and_then(|r| r.create())
+        .and_then(|r| r.add_rules(path_beneath_rules(&read_paths, read)))
+        .and_then(|r| r.add_rules(path_beneath_rules(&write_paths, write)))
+        .and_then(|r| {
+            r.add_rules(path_beneath_rules(
+                [config::state_dir()],
+                AccessFs::from_all(abi),
+            ))
+        })
+        .and_then(|r| r.restrict_self())
+        .map_err(|e| format!("failed to set up the sandbox: {}", e))?;
+
+    match status.ruleset {
+        RulesetStatus::FullyEnforced => info!("Filesystem sandbox enabled"),
+        RulesetStatus::PartiallyEnforced => {
+            info!("Filesystem sandbox enabled with this kernel's older Landlock")
+        }
+        RulesetStatus::NotEnforced => {
+            warn!("Landlock is not available; running without a filesystem sandbox")
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::quirks;
+use crate::sysfs;
+
+/// hwmon drivers whose readings come from the CPU package.
+const CPU_CHIPS: &[&str] = &["coretemp", "k10temp", "zenpower"];
+/// Thermal zone types that measure the CPU package.
+const CPU_ZONES: &[&str] = &["x86_pkg_temp", "cpu-thermal", "TCPU"];
+
+pub struct FanReading {
+    pub label: String,
+    pub rpm: u32,
+}
+
+pub struct TempReading {
+    pub source: String,
+    pub label: String,
+    pub celsius: f64,
+    pub is_cpu: bool,
+}
+
+/// Lists every hwmon device as (directory, driver name).
+pub fn hwmon_devices() -> Vec<(PathBuf, String)> {
+    let Ok(entries) = fs::read_dir(sysfs::path("/sys/class/hwmon")) else {
+        return Vec::new();
+    };
+
+    let mut devices: Vec<(PathBuf, String)> = entries
+        .flatten()
+        .map(|entry| {
+            let dir = entry.path();
+            let name = read_trimmed(&dir.join("name")).unwrap_or_default();
+            (dir, name)
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// Returns the channel numbers N for which `<prefix>N_input` exists in `dir`.
+pub fn channels(dir: &Path, prefix: &str) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut channels: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_prefix(prefix)?
+                .strip_suffix("_input")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    channels.sort();
+    channels
+}
+
+pub fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Every fan exposed through hwmon, with the ASUS platform fans first.
+pub fn fans() -> Vec<FanReading> {
+    let mut devices = hwmon_devices();
+    devices.sort_by_key(|(_, name)| name != "asus");
+
+    let mut readings = Vec::new();
+    for (dir, name) in devices {
+        for n in channels(&dir, "fan") {
+            let Some(rpm) = read_trimmed(&dir.join(format!("fan{}_input", n)))
+                .and_then(|s| s.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let label = read_trimmed(&dir.join(format!("fan{}_label", n)))
+                .unwrap_or_else(|| format!("{} fan{}", name, n));
+            let rpm = match quirks::get().fan_rpm_multiplier {
+                Some(m) if name == "asus" => rpm * m,
+                _ => rpm,
+            };
+            readings.push(FanReading { label, rpm });
+        }
+    }
+    readings
+}
+
+pub fn get_fans(readings: &[FanReading]) -> Result<String, String> {
+    if readings.is_empty() {
+        return Err("no fans found".into());
+    }
+
+    Ok(readings
+        .iter()
+        .map(|f| format!("{}: {}", f.label, f.rpm))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+pub fn temperatures() -> Vec<TempReading> {
+    let mut readings = Vec::new();
+
+    for (dir, name) in hwmon_devices() {
+        for n in channels(&dir, "temp") {
+            let Some(millis) = read_trimmed(&dir.join(format!("temp{}_input", n)))
+                .and_then(|s| s.parse::<i64>().ok())
+            else {
+                continue;
+            };
+            let label = read_trimmed(&dir.join(format!("temp{}_label", n)))
+                .unwrap_or_else(|| format!("temp{}", n));
+            readings.push(TempReading {
+                source: name.clone(),
+                label,
+                celsius: millis as f64 / 1000.0,
+                is_cpu: CPU_CHIPS.contains(&name.as_str()),
+            });
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(sysfs::path("/sys/class/thermal")) {
+        let mut zones: Vec<PathBuf> = entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|f| f.starts_with("thermal_zone"))
+            })
+            .collect();
+        zones.sort();
+
+        for zone in zones {
+            let Some(millis) = read_trimmed(&zone.join("temp")).and_then(|s| s.parse::<i64>().ok())
+            else {
+                continue;
+            };
+            let kind = read_trimmed(&zone.join("type")).unwrap_or_default();
+            readings.push(TempReading {
+                source: zone.file_name().unwrap().to_string_lossy().into_owned(),
+                is_cpu: CPU_ZONES.contains(&kind.as_str()),
+                label: kind,
+                celsius: millis as f64 / 1000.0,
+            });
+        }
+    }
+
+    readings
+}
+
+pub fn hottest_cpu(readings: &[TempReading]) -> Option<&TempReading> {
+    readings
+        .iter()
+        .filter(|r| r.is_cpu)
+        .max_by(|a, b| a.celsius.total_cmp(&b.celsius))
+}
+
+/// One line per sensor, preceded by the hottest CPU sensor as `cpu: <celsius>`.
+pub fn get_temps(readings: &[TempReading]) -> Result<String, String> {
+    if readings.is_empty() {
+        return Err("no temperature sensors found".into());
+    }
+
+    let mut lines = Vec::new();
+    if let Some(hottest) = hottest_cpu(readings) {
+        lines.push(format!("cpu: {:.1}", hottest.celsius));
+    }
+    for r in readings {
+        lines.push(format!("{}/{}: {:.1}", r.source, r.label, r.celsius));
+    }
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,338 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::sensors::read_trimmed;
+use crate::sysfs;
+
+/// Seconds of simulated time per step of the model.
+const STEP: Duration = Duration::from_secs(1);
+
+/// The parts of the simulated laptop that aren't plain sysfs nodes.
+struct Model {
+    ac: bool,
+    /// Battery charge in percent.
+    capacity: f64,
+    cpu_temp: f64,
+    cpu_fan: f64,
+    gpu_fan: f64,
+}
+
+static MODEL: Mutex<Option<Model>> = Mutex::new(None);
+
+/// Nodes whose value is one of several bracketed options, which writes collapse to one word.
+const CHOICE_NODES: &[(&str, &str)] = &[
+    (
+        "sys/module/pcie_aspm/parameters/policy",
+        "default performance powersave powersupersave",
+    ),
+    ("sys/power/mem_sleep", "s2idle deep"),
+];
+
+pub fn active() -> bool {
+    MODEL.lock().unwrap().is_some()
+}
+
+/// Creates a new directory only the daemon's user can enter, so other users can't plant
+/// symlinks in the tree and concurrent simulations don't share one.
+fn private_dir() -> Result<PathBuf, String> {
+    let base = std::env::temp_dir();
+    for n in 0.. {
+        let dir = base.join(format!(
+            "asus-control-simulate-{}-{}",
+            std::process::id(),
+            n
+        ));
+        match fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("failed to create {}: {}", dir.display(), e)),
+        }
+    }
+    unreachable!()
+}
+
+/// Forks a process that removes `root` once the daemon has exited, however it exits. It keeps
+/// the privileges the sandbox takes from the daemon, which could no longer remove the tree.
+/// Must run before any thread is spawned.
+fn remove_on_exit(root: &Path) -> Result<(), String> {
+    let mut fds = [0; 2];
+    // SAFETY: pipe2 writes two new descriptors into the array.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(format!(
+            "failed to watch {}: {}",
+            root.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    let [read, write] = fds;
+
+    // SAFETY: the process is still single-threaded, so the child is a consistent copy.
+    match unsafe { libc::fork() } {
+        -1 => Err(format!(
+            "failed to watch {}: {}",
+            root.display(),
+            std::io::Error::last_os_error()
+        )),
+        0 => {
+            // SAFETY: setsid and close only affect this process. Its own session keeps a
+            // Ctrl-C meant for the daemon from reaching it.
+            unsafe {
+                libc::setsid();
+                libc::close(write);
+            }
+            // The daemon never writes, so the read returns once its end of the pipe closes.
+            let mut byte = 0u8;
+            // SAFETY: the buffer is one valid byte.
+            while unsafe { libc::read(read, (&mut byte as *mut u8).cast(), 1) } == -1
+                && std::io::Error::last_os_error().kind() == ErrorKind::Interrupted
+            {}
+            let _ = fs::remove_dir_all(root);
+            // SAFETY: _exit skips the daemon's atexit handlers, which belong to the parent.
+            unsafe { libc::_exit(0) }
+        }
+        _ => {
+            // SAFETY: the child has its own copy; the write end stays open until exit.
+            unsafe { libc::close(read) };
+            Ok(())
+        }
+    }
+}
+
+/// Builds a fake sysfs tree for an ASUS laptop in a fresh private directory and points the
+/// daemon at it. Returns the tree's root, which is removed when the daemon exits; [`spawn`]
+/// then starts the model that drives it. Must run before any thread is spawned.
+pub fn init() -> Result<PathBuf, String> {
+    let root = private_dir()?;
+    remove_on_exit(&root)?;
+    populate(&root).map_err(|e| format!("failed to build {}: {}", root.display(), e))?;
+
+    *MODEL.lock().unwrap() = Some(Model {
+        ac: true,
+        capacity: 62.0,
+        cpu_temp: 48.0,
+        cpu_fan: 2400.0,
+        gpu_fan: 2100.0,
+    });
+    sysfs::set_root(root.clone());
+    info!("Simulating an ASUS laptop in {}", root.display());
+    Ok(root)
+}
+
+pub fn spawn(root: PathBuf) {
+    thread::spawn(move || {
+        loop {
+            step(&root);
+            thread::sleep(STEP);
+        }
+    });
+}
+
+fn populate(root: &Path) -> std::io::Result<()> {
+    let node = |path: &str, value: &str| -> std::io::Result<()> {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, format!("{}\n", value))
+    };
+
+    node("sys/class/dmi/id/sys_vendor", "ASUSTeK COMPUTER INC.")?;
+    node("sys/class/dmi/id/product_name", "Simulated ASUS Laptop")?;
+    node("sys/class/dmi/id/board_name", "SIM0000")?;
+    node("sys/class/dmi/id/bios_version", "SIM.100")?;
+
+    node("sys/firmware/acpi/platform_profile", "balanced")?;
+    node(
+        "sys/firmware/acpi/platform_profile_choices",
+        "quiet balanced performance",
+    )?;
+
+    let bat = "sys/class/power_supply/BAT0";
+    node(&format!("{}/type", bat), "Battery")?;
+    node(&format!("{}/charge_control_end_threshold", bat), "80")?;
+    node(&format!("{}/capacity", bat), "62")?;
+    node(&format!("{}/status", bat), "Charging")?;
+    node(&format!("{}/power_now", bat), "0")?;
+    node("sys/class/power_supply/AC0/type", "Mains")?;
+    node("sys/class/power_supply/AC0/online", "1")?;
+
+    let asus = "sys/class/hwmon/hwmon0";
+    node(&format!("{}/name", asus), "asus")?;
+    node(&format!("{}/fan1_label", asus), "cpu_fan")?;
+    node(&format!("{}/fan1_input", asus), "2400")?;
+    node(&format!("{}/fan2_label", asus), "gpu_fan")?;
+    node(&format!("{}/fan2_input", asus), "2100")?;
+    let cpu = "sys/class/hwmon/hwmon1";
+    node(&format!("{}/name", cpu), "coretemp")?;
+    node(&format!("{}/temp1_label", cpu), "Package id 0")?;
+    node(&format!("{}/temp1_input", cpu), "48000")?;
+    let curves = "sys/class/hwmon/hwmon2";
+    node(&format!("{}/name", curves), "asus_custom_fan_curve")?;
+    for pwm in 1..=2 {
+        node(&format!("{}/pwm{}_enable", curves, pwm), "2")?;
+        for point in 1..=8 {
+            node(
+                &format!("{}/pwm{}_auto_point{}_temp", curves, pwm, point),
+                &(30 + point * 8).to_string(),
+            )?;
+            node(
+                &format!("{}/pwm{}_auto_point{}_pwm", curves, pwm, point),
+                &(point * 31).to_string(),
+            )?;
+        }
+    }
+
+    let kbd = "sys/class/leds/asus::kbd_backlight";
+    node(&format!("{}/brightness", kbd), "1")?;
+    node(&format!("{}/max_brightness", kbd), "3")?;
+
+    let attrs = "sys/class/firmware-attributes/asus-armoury/attributes";
+    node(&format!("{}/ppt_pl1_spl/type", attrs), "integer")?;
+    node(&format!("{}/ppt_pl1_spl/current_value", attrs), "45")?;
+    node(&format!("{}/ppt_pl1_spl/min_value", attrs), "15")?;
+    node(&format!("{}/ppt_pl1_spl/max_value", attrs), "80")?;
+    node(&format!("{}/ppt_pl1_spl/scalar_increment", attrs), "1")?;
+    node(
+        &format!("{}/ppt_pl1_spl/display_name", attrs),
+        "CPU sustained power limit",
+    )?;
+    node(&format!("{}/panel_overdrive/type", attrs), "enumeration")?;
+    node(&format!("{}/panel_overdrive/current_value", attrs), "0")?;
+    node(&format!("{}/panel_overdrive/possible_values", attrs), "0;1")?;
+    node(
+        &format!("{}/panel_overdrive/display_name", attrs),
+        "Panel overdrive",
+    )?;
+
+    for n in 0..4 {
+        let policy = format!("sys/devices/system/cpu/cpu{}/cpufreq", n);
+        node(&format!("{}/scaling_governor", policy), "powersave")?;
+        node(
+            &format!("{}/scaling_available_governors", policy),
+            "performance powersave",
+        )?;
+        node(
+            &format!("{}/energy_performance_preference", policy),
+            "balance_performance",
+        )?;
+        node(
+            &format!("{}/energy_performance_available_preferences", policy),
+            "default performance balance_performance balance_power power",
+        )?;
+    }
+    node("sys/devices/system/cpu/cpufreq/boost", "1")?;
+
+    node(
+        CHOICE_NODES[0].0,
+        "[default] performance powersave powersupersave",
+    )?;
+    node(CHOICE_NODES[1].0, "s2idle [deep]")?;
+    node("proc/sys/kernel/nmi_watchdog", "1")?;
+    Ok(())
+}
+
+/// Advances the model by one step: temperatures and fans settle toward what the active
+/// profile would give, and the battery charges up to the threshold on AC or drains without.
+fn step(root: &Path) {
+    let read = |path: &str| read_trimmed(&root.join(path));
+    let write = |path: &str, value: String| {
+        if let Err(e) = fs::write(root.join(path), value) {
+            warn!("Failed to update simulated {}: {}", path, e);
+        }
+    };
+
+    let (temp, cpu_fan, gpu_fan, draw) = match read("sys/firmware/acpi/platform_profile").as_deref()
+    {
+        Some("quiet") => (42.0, 1800.0, 1500.0, 7.0),
+        Some("performance") => (78.0, 4600.0, 4200.0, 28.0),
+        _ => (56.0, 2800.0, 2500.0, 14.0),
+    };
+    let threshold = read("sys/class/power_supply/BAT0/charge_control_end_threshold")
+        .and_then(|s| s.parse::<f64>().ok())
+        .unwrap_or(100.0);
+
+    let mut model = MODEL.lock().unwrap();
+    let Some(m) = model.as_mut() else {
+        return;
+    };
+    m.cpu_temp += (temp - m.cpu_temp) * 0.2;
+    m.cpu_fan += (cpu_fan - m.cpu_fan) * 0.3;
+    m.gpu_fan += (gpu_fan - m.gpu_fan) * 0.3;
+
+    let (status, watts) = if !m.ac {
+        m.capacity = (m.capacity - draw / 200.0).max(0.0);
+        ("Discharging", draw)
+    } else if m.capacity < threshold {
+        m.capacity = (m.capacity + 0.5).min(threshold);
+        ("Charging", 35.0)
+    } else {
+        m.capacity = (m.capacity - 0.05).max(threshold);
+        ("Not charging", 0.0)
+    };
+
+    write(
+        "sys/class/hwmon/hwmon1/temp1_input",
+        format!("{:.0}", m.cpu_temp * 1000.0),
+    );
+    write(
+        "sys/class/hwmon/hwmon0/fan1_input",
+        format!("{:.0}", m.cpu_fan),
+    );
+    write(
+        "sys/class/hwmon/hwmon0/fan2_input",
+        format!("{:.0}", m.gpu_fan),
+    );
+    write(
+        "sys/class/power_supply/BAT0/capacity",
+        format!("{:.0}", m.capacity),
+    );
+    write("sys/class/power_supply/BAT0/status", status.to_string());
+    write(
+        "sys/class/power_supply/BAT0/power_now",
+        format!("{:.0}", watts * 1e6),
+    );
+    write(
+        "sys/class/power_supply/AC0/online",
+        (m.ac as u8).to_string(),
+    );
+
+    // Writing an option replaces the whole node; put the list back with it selected.
+    for (path, options) in CHOICE_NODES {
+        if let Some(value) = read(path)
+            && !value.contains('[')
+        {
+            let listed = options
+                .split_whitespace()
+                .map(|o| match o == value {
+                    true => format!("[{}]", o),
+                    false => o.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            write(path, listed);
+        }
+    }
+}
+
+/// `simulate ac <online|offline|toggle>`: plugs or unplugs the simulated charger.
+pub fn set_ac(state: &str) -> Result<String, String> {
+    let mut model = MODEL.lock().unwrap();
+    let m = model
+        .as_mut()
+        .ok_or_else(|| "not running with --simulate".to_string())?;
+    m.ac = match state {
+        "online" => true,
+        "offline" => false,
+        "toggle" => !m.ac,
+        _ => return Err("invalid AC state - use online, offline or toggle".into()),
+    };
+    Ok(format!(
+        "Simulated AC {}",
+        if m.ac { "online" } else { "offline" }
+    ))
+}
@@ -0,0 +1,90 @@
+use crate::backend;
+use crate::conflicts;
+use crate::get_battery_threshold;
+use crate::keyboard;
+use crate::profile::get_fan_profile;
+use crate::sampler;
+use crate::sensors;
+
+fn or_unavailable(r: Result<String, String>) -> String {
+    r.unwrap_or_else(|_| "unavailable".into())
+}
+
+/// Everything the daemon knows, one `key: value` line each. Fans and temperatures use
+/// `fan.<label>` and `temp.<source>/<label>` keys, battery power is in watts, and unreadable
+/// values are `unavailable`.
+pub fn get_status() -> String {
+    let mut lines = Vec::new();
+    let mut push = |key: &str, value: String| lines.push(format!("{}: {}", key, value));
+
+    let backend = backend::get();
+    push("profile", or_unavailable(get_fan_profile()));
+    push(
+        "backend",
+        backend.profile_interface().unwrap_or("unavailable").into(),
+    );
+    let choices = backend.profile_choices();
+    push(
+        "choices",
+        match choices.is_empty() {
+            true => "unavailable".into(),
+            false => choices
+                .iter()
+                .map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        },
+    );
+
+    push("battery-threshold", or_unavailable(get_battery_threshold()));
+    let battery = backend.battery();
+    if let Some(start) = battery.start_threshold {
+        push("charge-start-threshold", start.to_string());
+    }
+    push(
+        "battery-capacity",
+        battery
+            .capacity
+            .map_or_else(|| "unavailable".into(), |c| c.to_string()),
+    );
+    push(
+        "battery-status",
+        battery.status.unwrap_or_else(|| "unavailable".into()),
+    );
+    let sample = sampler::latest();
+    push(
+        "battery-power",
+        sample
+            .battery_power
+            .map_or_else(|| "unavailable".into(), |w| format!("{:.1}", w)),
+    );
+    push(
+        "ac",
+        match backend.ac_online() {
+            Some(true) => "online".into(),
+            Some(false) => "offline".into(),
+            None => "unavailable".into(),
+        },
+    );
+
+    for fan in &sample.fans {
+        push(&format!("fan.{}", fan.label), fan.rpm.to_string());
+    }
+    if let Some(hottest) = sensors::hottest_cpu(&sample.temps) {
+        push("cpu-temp", format!("{:.1}", hottest.celsius));
+    }
+    for t in &sample.temps {
+        push(
+            &format!("temp.{}/{}", t.source, t.label),
+            format!("{:.1}", t.celsius),
+        );
+    }
+
+    push(
+        "keyboard-backlight",
+        or_unavailable(keyboard::get_keyboard_backlight()),
+    );
+    push("conflicts", conflicts::summary());
+
+    lines.join("\n")
+}
@@ -0,0 +1,187 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use serde::de::DeserializeOwned;
+use tracing::{debug, warn};
+
+use crate::conflicts;
+
+/// Failed or mismatched sysfs writes since the daemon started.
+pub static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Directory standing in for `/` for sysfs paths, from `--sysfs-root`.
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Nodes opened for writing before privileges were dropped, for those like `/proc/sys` that
+/// check the writer's uid rather than its capabilities.
+static HELD: Mutex<BTreeMap<PathBuf, File>> = Mutex::new(BTreeMap::new());
+
+/// What nodes written through [`write_over_baseline`] held before the daemon first changed
+/// them.
+static BASELINE: Mutex<BTreeMap<PathBuf, String>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// Writes recorded instead of performed while a dry run is active on this thread.
+    static DRY_RUN: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    /// Whether this thread attempted a write since [`track_writes`] last started.
+    static WROTE: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_root(root: PathBuf) {
+    let _ = ROOT.set(root);
+}
+
+/// Resolves an absolute `/sys` or `/proc/sys` path, under `--sysfs-root` when one was given.
+pub fn path(path: &str) -> PathBuf {
+    match ROOT.get() {
+        Some(root) => root.join(path.trim_start_matches('/')),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Opens `path` for writing now, so later writes still work once the daemon can't open it.
+pub fn hold(path: PathBuf) -> Result<(), String> {
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    HELD.lock().unwrap().insert(path, file);
+    Ok(())
+}
+
+pub fn is_held(path: &Path) -> bool {
+    HELD.lock().unwrap().contains_key(path)
+}
+
+/// Runs `f` without touching sysfs or state files, returning what it would have written.
+pub fn dry_run<T>(f: impl FnOnce() -> T) -> (T, Vec<String>) {
+    DRY_RUN.with(|d| *d.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let writes = DRY_RUN.with(|d| d.borrow_mut().take()).unwrap_or_default();
+    (result, writes)
+}
+
+/// Records the write to `target` if a dry run is active; returns whether it was recorded.
+/// Every writer calls this first, so a `false` also marks the write for [`track_writes`].
+pub fn record(target: &str, value: &str) -> bool {
+    let recorded = DRY_RUN.with(|d| match d.borrow_mut().as_mut() {
+        Some(writes) => {
+            writes.push(format!("would write {:?} to {}", value, target));
+            true
+        }
+        None => false,
+    });
+    if !recorded {
+        WROTE.set(true);
+    }
+    recorded
+}
+
+/// Runs `f` and returns whether it attempted any write, even one that failed halfway.
+pub fn track_writes<T>(f: impl FnOnce() -> T) -> (T, bool) {
+    let outer = WROTE.replace(false);
+    let result = f();
+    let wrote = WROTE.replace(outer);
+    WROTE.set(outer || wrote);
+    (result, wrote)
+}
+
+/// Returns the bracketed entry of an `a [b] c` style node, or the whole value.
+pub fn selected(raw: &str) -> &str {
+    raw.split_whitespace()
+        .find_map(|w| w.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or(raw)
+}
+
+/// Writes a sysfs node and reads it back, failing with a mismatch if the value did not stick.
+pub fn write(path: impl AsRef<Path>, value: &str) -> Result<(), String> {
+    let path = path.as_ref();
+    if record(&path.display().to_string(), value) {
+        return Ok(());
+    }
+
+    debug!(sysfs_path = %path.display(), value, "Writing sysfs node");
+    let result = conflicts::own_write(path, || write_verified(path, value));
+    if let Err(e) = &result {
+        warn!(sysfs_path = %path.display(), "{}", e);
+        WRITE_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+    result
+}
+
+/// Writes like [`write`], first remembering what `path` held if the daemon hasn't changed it
+/// yet, so [`restore_baseline`] can put that back.
+pub fn write_over_baseline(path: impl AsRef<Path>, value: &str) -> Result<(), String> {
+    let path = path.as_ref();
+    if DRY_RUN.with(|d| d.borrow().is_none())
+        && let Ok(raw) = fs::read_to_string(path)
+    {
+        BASELINE
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_insert_with(|| selected(raw.trim()).to_string());
+    }
+    write(path, value)
+}
+
+/// Puts back what `path` held before [`write_over_baseline`] first changed it, if it did.
+pub fn restore_baseline(path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    let Some(value) = BASELINE.lock().unwrap().get(path).cloned() else {
+        return Ok(());
+    };
+    write(path, &value)
+}
+
+fn write_verified(path: &Path, value: &str) -> Result<(), String> {
+    let written = match HELD.lock().unwrap().get(path) {
+        Some(file) => file.write_at(value.as_bytes(), 0).map(|_| ()),
+        None => fs::write(path, value),
+    };
+    written.map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read back {}: {}", path.display(), e))?;
+    let actual = selected(raw.trim());
+    if actual != value.trim() {
+        return Err(format!(
+            "mismatch: wrote {} to {} but read back {}",
+            value.trim(),
+            path.display(),
+            actual
+        ));
+    }
+    Ok(())
+}
+
+/// Reads one of the daemon's own state files; only a missing file means nothing was saved.
+/// Other failures are errors, so callers never write over a file they couldn't read.
+pub fn read_state<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> Result<T, String> {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text)
+            .map_err(|e| format!("invalid {}: {}", path.display(), e.message())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Writes one of the daemon's own state files; skipped during a dry run.
+pub fn write_state(path: impl AsRef<Path>, contents: &str) -> Result<(), String> {
+    let path = path.as_ref();
+    if record(&path.display().to_string(), contents) {
+        return Ok(());
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
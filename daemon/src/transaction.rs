@@ -0,0 +1,153 @@
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::fan_curve::{self, Fan};
+use crate::firmware_attrs;
+use crate::keyboard;
+use crate::profile::{PlatformProfile, get_fan_profile};
+use crate::sysfs;
+use crate::{get_battery_threshold, handle_command};
+
+static SETTINGS: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    static HOLDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Held by every writer, so no other one can slip in between a transaction's snapshot and its
+/// rollback, or between reading a setting and changing it. Taking it again on the same thread
+/// is a no-op.
+pub struct SettingsLock(Option<MutexGuard<'static, ()>>);
+
+impl Drop for SettingsLock {
+    fn drop(&mut self) {
+        if self.0.is_some() {
+            HOLDING.set(false);
+        }
+    }
+}
+
+pub fn lock() -> SettingsLock {
+    if HOLDING.get() {
+        return SettingsLock(None);
+    }
+    let guard = SETTINGS.lock().unwrap_or_else(PoisonError::into_inner);
+    HOLDING.set(true);
+    SettingsLock(Some(guard))
+}
+
+/// Builds the `set` command that restores what `cmd` is about to change.
+fn snapshot(cmd: &str) -> Result<String, String> {
+    let parts: Vec<&str> = cmd.split_whitespace().collect();
+    match parts.as_slice() {
+        ["set", "profile", ..] => Ok(format!("set profile {}", get_fan_profile()?)),
+        ["set", "battery-threshold", ..] => Ok(format!(
+            "set battery-threshold {}",
+            get_battery_threshold()?
+        )),
+        ["set", "keyboard-backlight", ..] => Ok(format!(
+            "set keyboard-backlight {}",
+            keyboard::get_keyboard_backlight()?
+        )),
+        ["set", "fan-curve", fan, _, rest @ ..] => {
+            let fan = Fan::parse(fan).ok_or("invalid fan - use cpu, gpu or mid")?;
+            let profile = match rest.first() {
+                Some(p) => PlatformProfile::parse(p).ok_or("invalid profile")?,
+                None => {
+                    let name = get_fan_profile()?;
+                    PlatformProfile::parse(&name)
+                        .ok_or_else(|| format!("unsupported active profile: {}", name))?
+                }
+            };
+            let curve = fan_curve::get_fan_curve(fan, Some(profile))?;
+            Ok(format!(
+                "set fan-curve {} {} {}",
+                fan.as_str(),
+                curve,
+                profile.as_str()
+            ))
+        }
+        ["set", "firmware-attr", name, ..] => Ok(format!(
+            "set firmware-attr {} {}",
+            name,
+            firmware_attrs::current_value(name)?
+        )),
+        ["set", target, ..] => Err(format!("{} cannot be part of a transaction", target)),
+        _ => Err("only set commands can be part of a transaction".into()),
+    }
+}
+
+/// Applies `commands` in order, undoing the applied ones in reverse if any fails.
+/// Returns whether everything was applied, and a summary line followed by one line per item.
+pub fn run(commands: &[String]) -> (bool, String) {
+    let _settings = lock();
+    if commands.is_empty() {
+        return (false, "error: empty transaction".into());
+    }
+
+    let mut items = Vec::new();
+    let mut undo: Vec<(usize, String)> = Vec::new();
+    let mut failed = None;
+
+    for (i, cmd) in commands.iter().enumerate() {
+        let restore = match snapshot(cmd) {
+            Ok(r) => r,
+            Err(e) => {
+                items.push(format!("failed: {}: {}", cmd, e));
+                failed = Some(i);
+                break;
+            }
+        };
+        let (response, wrote) = sysfs::track_writes(|| handle_command(cmd));
+        if let Some(e) = response.strip_prefix("error: ") {
+            items.push(format!("failed: {}: {}", cmd, e));
+            failed = Some(i);
+            // Whatever the failed command got to write is undone along with the rest.
+            if wrote {
+                handle_command(&restore);
+            }
+            break;
+        }
+        items.push(format!("ok: {}: {}", cmd, response));
+        undo.push((i, restore));
+    }
+
+    let Some(at) = failed else {
+        return (
+            true,
+            format!("ok: {} settings applied\n{}", items.len(), items.join("\n")),
+        );
+    };
+
+    let (mut restored, mut unrestored) = (0, 0);
+    for (i, restore) in undo.into_iter().rev() {
+        let response = handle_command(&restore);
+        items[i] = match response.strip_prefix("error: ") {
+            Some(e) => {
+                unrestored += 1;
+                format!("rollback-failed: {}: {}", commands[i], e)
+            }
+            None => {
+                restored += 1;
+                format!("rolled-back: {}", commands[i])
+            }
+        };
+    }
+    for cmd in &commands[at + 1..] {
+        items.push(format!("skipped: {}", cmd));
+    }
+
+    (
+        false,
+        format!(
+            "error: transaction failed at item {}, {} change(s) rolled back{}\n{}",
+            at + 1,
+            restored,
+            match unrestored {
+                0 => String::new(),
+                n => format!(", {} could not be", n),
+            },
+            items.join("\n")
+        ),
+    )
+}
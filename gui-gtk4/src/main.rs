@@ -5,6 +5,7 @@ use main_window::MainWindow;
 use adw::prelude::*;
 use gtk4::prelude::GtkWindowExt;
 use adw::Application;
+use asus_control_protocol::init_logging;
 
 fn build_ui(application: &Application) {
     let window = MainWindow::new(application);
@@ -12,10 +13,11 @@ fn build_ui(application: &Application) {
 }
 
 pub fn main() {
+    let args = init_logging(std::env::args().collect());
     let application = Application::builder()
         .application_id("dev.uncognic.asus-control-gui")
         .build();
 
     application.connect_activate(build_ui);
-    application.run();
+    application.run_with_args(&args);
 }
\ No newline at end of file
@@ -2,19 +2,25 @@ use super::MainWindow;
 
 use adw::ApplicationWindow;
 use adw::subclass::prelude::AdwApplicationWindowImpl;
+use asus_control_protocol::{self as protocol, DaemonInfo};
 use glib::{
     object_subclass,
     subclass::{InitializingObject, types::ObjectSubclass},
 };
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
-use gtk4::{CompositeTemplate, Label, Scale, TemplateChild, ToggleButton};
+use gtk4::{
+    Button, CompositeTemplate, DropDown, Entry, Expander, Label, ListBox, Scale, SpinButton, TemplateChild,
+    ToggleButton,
+};
+use std::collections::HashMap;
 use std::cell::RefCell;
-use std::io::{Read, Write};
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
+use tracing::{debug, warn};
 
 #[derive(CompositeTemplate, Default)]
 #[template(file = "../../content/main-window.ui")]
@@ -31,6 +37,33 @@ pub struct MainWindowTemplate {
     pub battery_value: TemplateChild<Label>,
     #[template_child]
     pub fan_rpm_label: TemplateChild<Label>,
+    #[template_child]
+    pub cpu_temp_label: TemplateChild<Label>,
+    #[template_child]
+    pub advanced_expander: TemplateChild<Expander>,
+    #[template_child]
+    pub advanced_list: TemplateChild<ListBox>,
+    #[template_child]
+    pub presets_box: TemplateChild<gtk4::Box>,
+    #[template_child]
+    pub daemon_banner: TemplateChild<adw::Banner>,
+}
+
+/// Sends one command to the daemon and returns its whole response.
+fn query(cmd: &str) -> Result<String, String> {
+    protocol::query(cmd).map_err(|e| format!("failed to talk to the daemon: {}", e))
+}
+
+/// Explains why the daemon can't serve this GUI, or None when it can.
+fn daemon_problem(hello: Result<DaemonInfo, String>) -> Option<String> {
+    match hello {
+        Err(e) => Some(format!("asus-control-daemon is not running ({})", e)),
+        Ok(info) if !info.is_current() => Some(format!(
+            "{} is too old for this GUI, please update it",
+            info.daemon
+        )),
+        Ok(_) => None,
+    }
 }
 
 #[object_subclass]
@@ -53,56 +86,84 @@ impl ObjectImpl for MainWindowTemplate {
         self.parent_constructed();
         let send_cmd: std::sync::Arc<dyn Fn(String) + Send + Sync + 'static> =
             std::sync::Arc::new(move |cmd: String| {
-                thread::spawn(
-                    move || match UnixStream::connect("/run/asus-control-daemon.sock") {
-                        Ok(mut stream) => {
-                            if let Err(e) = stream.write_all(cmd.as_bytes()) {
-                                eprintln!("Failed to write to daemon: {}", e);
-                                return;
-                            }
-                            if let Err(e) = stream.shutdown(std::net::Shutdown::Write) {
-                                eprintln!("Failed to shutdown write side of socket: {}", e);
-                            }
-                            let mut resp = String::new();
-                            if let Err(e) = stream.read_to_string(&mut resp) {
-                                eprintln!("Failed to read from daemon: {}", e);
-                            } else {
-                                eprintln!("Daemon response: {}", resp);
-                            }
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to connect to daemon socket: {}", e);
-                        }
-                    },
-                );
+                thread::spawn(move || match query(&cmd) {
+                    Ok(resp) => debug!("Daemon response: {}", resp),
+                    Err(e) => warn!("Failed to send {}: {}", cmd.trim(), e),
+                });
             });
 
+        let (hello_tx, hello_rx) = std::sync::mpsc::channel::<Result<DaemonInfo, String>>();
+        thread::spawn(move || {
+            let _ = hello_tx.send(protocol::hello().map_err(|e| e.to_string()));
+        });
+
+        let daemon_banner = self.daemon_banner.get();
+        let _hello_checker = glib::idle_add_local(move || match hello_rx.try_recv() {
+            Ok(hello) => {
+                if let Some(problem) = daemon_problem(hello) {
+                    daemon_banner.set_title(&problem);
+                    daemon_banner.set_revealed(true);
+                }
+                false.into()
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
+        });
+
+        let (attr_tx, attr_rx) = std::sync::mpsc::channel::<String>();
+        thread::spawn(move || match query("get firmware-attrs\n") {
+            Ok(resp) => {
+                let _ = attr_tx.send(resp);
+            }
+            Err(e) => warn!("Failed to get firmware-attrs: {}", e),
+        });
+
+        let advanced_expander = self.advanced_expander.get();
+        let advanced_list = self.advanced_list.get();
+        let send_cmd_for_attrs = send_cmd.clone();
+        let _attr_setter = glib::idle_add_local(move || match attr_rx.try_recv() {
+            Ok(s) => {
+                if !s.starts_with("error:") {
+                    for line in s.lines() {
+                        if let Some(row) = firmware_attr_row(line, send_cmd_for_attrs.clone()) {
+                            advanced_list.append(&row);
+                        }
+                    }
+                    advanced_expander.set_visible(advanced_list.first_child().is_some());
+                }
+                false.into()
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
+        });
+
         let slider = self.battery_slider.get();
         let fan_rpm_label = self.fan_rpm_label.get();
 
         let (tx, rx) = std::sync::mpsc::channel::<i32>();
-        thread::spawn(
-            move || match UnixStream::connect("/run/asus-control-daemon.sock") {
-                Ok(mut stream) => {
-                    if let Err(e) = stream.write_all(b"get battery-threshold\n") {
-                        eprintln!("Failed to write to daemon: {}", e);
-                        return;
+        let (ptx, prx) = std::sync::mpsc::channel::<String>();
+        thread::spawn(move || {
+            let resp = match query("get status\n") {
+                Ok(resp) => resp,
+                Err(e) => {
+                    warn!("Failed to get status: {}", e);
+                    return;
+                }
+            };
+            for (key, value) in resp.lines().filter_map(|l| l.split_once(": ")) {
+                match key {
+                    "battery-threshold" => {
+                        if let Ok(n) = value.trim().parse::<i32>() {
+                            let _ = tx.send(n);
+                        }
                     }
-                    let _ = stream.shutdown(std::net::Shutdown::Write);
-                    let mut resp = String::new();
-                    if let Err(e) = stream.read_to_string(&mut resp) {
-                        eprintln!("Failed to read from daemon: {}", e);
-                    } else if let Ok(n) = resp.trim().parse::<i32>() {
-                        let _ = tx.send(n);
-                    } else {
-                        eprintln!("Daemon response for get battery-threshold: {}", resp);
+                    "profile" => {
+                        let _ = ptx.send(value.to_string());
                     }
+                    _ => {}
                 }
-                Err(e) => {
-                    eprintln!("Failed to connect to daemon socket: {}", e);
-                }
-            },
-        );
+            }
+        });
 
         let slider_clone_for_init = slider.clone();
         let (value_tx, value_rx) = std::sync::mpsc::channel::<i32>();
@@ -125,79 +186,71 @@ impl ObjectImpl for MainWindowTemplate {
             Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
         });
 
-        let (fr_tx, fr_rx) = std::sync::mpsc::channel::<String>();
-        let fetch_fan_rpm: Arc<dyn Fn() + Send + Sync + 'static> = Arc::new(move || {
-            let fr_tx = fr_tx.clone();
-            thread::spawn(
-                move || match UnixStream::connect("/run/asus-control-daemon.sock") {
+        // One long-lived subscription instead of polling; the daemon pushes a block of
+        // `key: value` lines, ended by an empty line, every time it samples the sensors.
+        let (sample_tx, sample_rx) = std::sync::mpsc::channel::<String>();
+        thread::spawn(move || {
+            loop {
+                match UnixStream::connect(protocol::socket_path()) {
                     Ok(mut stream) => {
-                        if let Err(e) = stream.write_all(b"get fan-speed-rpm\n") {
-                            eprintln!("Failed to write to daemon: {}", e);
-                            return;
-                        }
-                        let _ = stream.shutdown(std::net::Shutdown::Write);
-                        let mut resp = String::new();
-                        if let Err(e) = stream.read_to_string(&mut resp) {
-                            eprintln!("Failed to read from daemon: {}", e);
-                        } else {
-                            println!("Daemon response for get fan-speed-rpm: {}", resp.trim());
-                            let _ = fr_tx.send(resp);
+                        if stream.write_all(b"subscribe\n").is_ok()
+                            && stream.shutdown(std::net::Shutdown::Write).is_ok()
+                        {
+                            let mut block = String::new();
+                            for line in BufReader::new(stream).lines() {
+                                let Ok(line) = line else { break };
+                                if !line.is_empty() {
+                                    block.push_str(&line);
+                                    block.push('\n');
+                                } else if sample_tx.send(std::mem::take(&mut block)).is_err() {
+                                    return;
+                                }
+                            }
                         }
                     }
                     Err(e) => {
-                        eprintln!("Failed to connect to daemon socket: {}", e);
+                        debug!("Failed to connect to daemon socket: {}", e);
                     }
-                },
-            );
+                }
+                thread::sleep(std::time::Duration::from_secs(5));
+            }
         });
-        fetch_fan_rpm();
-
-        let fetch_fan_rpm_for_timer = fetch_fan_rpm.clone();
-        let _fan_rpm_timer = glib::timeout_add_local(
-            std::time::Duration::from_secs(5),
-            move || {
-                fetch_fan_rpm_for_timer();
-                true.into()
-            },
-        );
 
+        let cpu_temp_label = self.cpu_temp_label.get();
         let fan_rpm_label_for_idle = fan_rpm_label.clone();
-        let _fan_rpm_setter = glib::idle_add_local(move || match fr_rx.try_recv() {
-            Ok(s) => {
-                let rpm = s.trim();
-                fan_rpm_label_for_idle.set_label(&format!("Fans: {} RPM", rpm));
-                false.into()
-            }
-            Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
-            Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
-        });
-        let slider_for_send = self.battery_slider.get();
-        let silent_btn = self.btn_silent.get();
-        let balanced_btn = self.btn_balanced.get();
-        let performance_btn = self.btn_performance.get();
+        let _sample_setter = glib::timeout_add_local(
+            std::time::Duration::from_millis(250),
+            move || match sample_rx.try_recv() {
+                Ok(s) => {
+                    let cpu = s
+                        .lines()
+                        .find_map(|l| l.strip_prefix("cpu-temp: "))
+                        .map(|t| format!("CPU: {} °C", t.trim()))
+                        .unwrap_or_else(|| "CPU: --".into());
+                    cpu_temp_label.set_label(&cpu);
 
-        let (ptx, prx) = std::sync::mpsc::channel::<String>();
-        thread::spawn(
-            move || match UnixStream::connect("/run/asus-control-daemon.sock") {
-                Ok(mut stream) => {
-                    if let Err(e) = stream.write_all(b"get profile\n") {
-                        eprintln!("Failed to write to daemon: {}", e);
-                        return;
-                    }
-                    let _ = stream.shutdown(std::net::Shutdown::Write);
-                    let mut resp = String::new();
-                    if let Err(e) = stream.read_to_string(&mut resp) {
-                        eprintln!("Failed to read from daemon: {}", e);
+                    let fans = s
+                        .lines()
+                        .filter_map(|l| l.strip_prefix("fan."))
+                        .filter_map(|l| l.split_once(": "))
+                        .map(|(label, rpm)| format!("{} {} RPM", label, rpm.trim()))
+                        .collect::<Vec<_>>();
+                    if fans.is_empty() {
+                        fan_rpm_label_for_idle.set_label("Fans: --");
                     } else {
-                        println!("Daemon response for get profile: {}", resp.trim());
-                        let _ = ptx.send(resp);
+                        fan_rpm_label_for_idle.set_label(&format!("Fans: {}", fans.join(", ")));
                     }
+                    true.into()
                 }
-                Err(e) => {
-                    eprintln!("Failed to connect to daemon socket: {}", e);
-                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
             },
         );
+        let slider_for_send = self.battery_slider.get();
+        let silent_btn = self.btn_silent.get();
+        let balanced_btn = self.btn_balanced.get();
+        let performance_btn = self.btn_performance.get();
+
 
         let silent_for_idle = silent_btn.clone();
         let balanced_for_idle = balanced_btn.clone();
@@ -207,7 +260,7 @@ impl ObjectImpl for MainWindowTemplate {
         let _profile_setter = glib::idle_add_local(move || match prx.try_recv() {
             Ok(s) => {
                 *suppress_for_idle.borrow_mut() = true;
-                match s.trim() {
+                match s.lines().next().unwrap_or("").trim() {
                     "quiet" => {
                         silent_for_idle.set_active(true);
                         balanced_for_idle.set_active(false);
@@ -231,6 +284,119 @@ impl ObjectImpl for MainWindowTemplate {
             Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
             Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
         });
+        let (caps_tx, caps_rx) = std::sync::mpsc::channel::<String>();
+        thread::spawn(move || match query("get capabilities\n") {
+            Ok(resp) => {
+                let _ = caps_tx.send(resp);
+            }
+            Err(e) => warn!("Failed to get capabilities: {}", e),
+        });
+
+        let slider_for_caps = slider.clone();
+        let battery_value_for_caps = self.battery_value.get();
+        let fan_rpm_label_for_caps = fan_rpm_label.clone();
+        let profile_buttons = [
+            ("quiet", silent_btn.clone()),
+            ("balanced", balanced_btn.clone()),
+            ("performance", performance_btn.clone()),
+        ];
+        let _caps_setter = glib::idle_add_local(move || match caps_rx.try_recv() {
+            Ok(s) => {
+                let caps: HashMap<&str, &str> =
+                    s.lines().filter_map(|l| l.split_once(": ")).collect();
+                if caps.get("battery-threshold") == Some(&"unavailable") {
+                    slider_for_caps.set_sensitive(false);
+                    battery_value_for_caps.set_label("n/a");
+                }
+                if caps.get("fans") == Some(&"unavailable") {
+                    fan_rpm_label_for_caps.set_visible(false);
+                }
+                if let Some(profile) = caps.get("profile") {
+                    let choices = profile
+                        .split_whitespace()
+                        .find_map(|kv| kv.strip_prefix("choices="))
+                        .unwrap_or("");
+                    for (name, btn) in &profile_buttons {
+                        btn.set_sensitive(choices.split(',').any(|c| c == *name));
+                    }
+                }
+                false.into()
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
+        });
+
+        let (presets_tx, presets_rx) = std::sync::mpsc::channel::<String>();
+        thread::spawn(move || match query("get presets\n") {
+            Ok(resp) => {
+                let _ = presets_tx.send(resp);
+            }
+            Err(e) => warn!("Failed to get presets: {}", e),
+        });
+
+        // After a preset is applied, the profile and threshold it left behind are read back here.
+        let (applied_tx, applied_rx) = std::sync::mpsc::channel::<(String, String)>();
+        let presets_box = self.presets_box.get();
+        let _presets_setter = glib::idle_add_local(move || match presets_rx.try_recv() {
+            Ok(s) => {
+                if !s.starts_with("error:") && s.trim() != "none" {
+                    for name in s.lines().filter_map(|l| l.split_once(": ").map(|(n, _)| n)) {
+                        let button = Button::with_label(name);
+                        let name = name.to_string();
+                        let applied_tx = applied_tx.clone();
+                        button.connect_clicked(move |_| {
+                            let name = name.clone();
+                            let applied_tx = applied_tx.clone();
+                            thread::spawn(move || {
+                                match query(&format!("apply preset {}\n", name)) {
+                                    Ok(resp) => debug!("Daemon response: {}", resp),
+                                    Err(e) => warn!("Failed to apply preset {}: {}", name, e),
+                                }
+                                let profile = query("get profile\n").unwrap_or_default();
+                                let threshold =
+                                    query("get battery-threshold\n").unwrap_or_default();
+                                let _ = applied_tx.send((profile, threshold));
+                            });
+                        });
+                        presets_box.append(&button);
+                    }
+                    presets_box.set_visible(presets_box.first_child().is_some());
+                }
+                false.into()
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
+        });
+
+        let slider_for_presets = slider.clone();
+        let suppress_for_presets = suppress_profile_signals.clone();
+        let buttons_for_presets = [
+            ("quiet", silent_btn.clone()),
+            ("balanced", balanced_btn.clone()),
+            ("performance", performance_btn.clone()),
+        ];
+        let _applied_setter = glib::timeout_add_local(
+            std::time::Duration::from_millis(250),
+            move || match applied_rx.try_recv() {
+                Ok((profile, threshold)) => {
+                    let profile = profile.lines().next().unwrap_or("").trim().to_string();
+                    if buttons_for_presets.iter().any(|(n, _)| *n == profile) {
+                        *suppress_for_presets.borrow_mut() = true;
+                        for (name, btn) in &buttons_for_presets {
+                            btn.set_active(*name == profile);
+                        }
+                        *suppress_for_presets.borrow_mut() = false;
+                    }
+                    if let Ok(n) = threshold.trim().parse::<f64>() {
+                        slider_for_presets.set_value(n);
+                    }
+                    true.into()
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => true.into(),
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => false.into(),
+            },
+        );
+
         let value_label = self.battery_value.get();
         let send_cmd_for_slider = send_cmd.clone();
         let pending: Rc<RefCell<Option<glib::source::SourceId>>> = Rc::new(RefCell::new(None));
@@ -306,6 +472,89 @@ impl ObjectImpl for MainWindowTemplate {
     }
 }
 
+/// Builds an editor row for one `get firmware-attrs` line, picking the widget from its type.
+fn firmware_attr_row(
+    line: &str,
+    send_cmd: Arc<dyn Fn(String) + Send + Sync + 'static>,
+) -> Option<gtk4::Box> {
+    let (name, rest) = line.split_once(": ")?;
+    let (meta, display_name) = rest.split_once(" display_name=")?;
+    // The current value comes last before the display name and is quoted when it has spaces.
+    let (meta, current) = meta.split_once(" current=")?;
+    let current = current
+        .strip_prefix('"')
+        .and_then(|c| c.strip_suffix('"'))
+        .unwrap_or(current);
+    let meta: HashMap<&str, &str> = meta
+        .split_whitespace()
+        .filter_map(|kv| kv.split_once('='))
+        .collect();
+    let name = name.to_string();
+
+    let row = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    row.set_margin_top(3);
+    row.set_margin_bottom(3);
+    let label = Label::new(Some(display_name));
+    label.set_halign(gtk4::Align::Start);
+    label.set_hexpand(true);
+    label.set_tooltip_text(Some(&name));
+    row.append(&label);
+
+    match *meta.get("type")? {
+        "integer" => {
+            let min = meta.get("min")?.parse::<f64>().ok()?;
+            let max = meta.get("max")?.parse::<f64>().ok()?;
+            let step = meta.get("step")?.parse::<f64>().ok()?;
+            let spin = SpinButton::with_range(min, max, step);
+            spin.set_value(current.parse::<f64>().ok()?);
+            // Like the battery slider, only the value the spinner settles on is written, so
+            // holding an arrow doesn't hammer the firmware with every step.
+            let pending: Rc<RefCell<Option<glib::source::SourceId>>> = Rc::new(RefCell::new(None));
+            spin.connect_value_changed(move |s| {
+                if let Some(id) = pending.borrow_mut().take() {
+                    id.remove();
+                }
+
+                let value = s.value_as_int();
+                let name = name.clone();
+                let sc = send_cmd.clone();
+                let pending_for_timeout = pending.clone();
+                let id = glib::timeout_add_local(std::time::Duration::from_millis(300), move || {
+                    sc(format!("set firmware-attr {} {}\n", name, value));
+                    *pending_for_timeout.borrow_mut() = None;
+                    false.into()
+                });
+                *pending.borrow_mut() = Some(id);
+            });
+            row.append(&spin);
+        }
+        "enumeration" => {
+            let values: Vec<&str> = meta.get("values")?.split(';').collect();
+            let dropdown = DropDown::from_strings(&values);
+            if let Some(i) = values.iter().position(|v| *v == current) {
+                dropdown.set_selected(i as u32);
+            }
+            let values: Vec<String> = values.into_iter().map(String::from).collect();
+            dropdown.connect_selected_notify(move |d| {
+                if let Some(v) = values.get(d.selected() as usize) {
+                    send_cmd(format!("set firmware-attr {} {}\n", name, v));
+                }
+            });
+            row.append(&dropdown);
+        }
+        _ => {
+            let entry = Entry::new();
+            entry.set_text(current);
+            entry.connect_activate(move |e| {
+                send_cmd(format!("set firmware-attr {} {}\n", name, e.text()));
+            });
+            row.append(&entry);
+        }
+    }
+
+    Some(row)
+}
+
 impl WidgetImpl for MainWindowTemplate {}
 impl WindowImpl for MainWindowTemplate {}
 impl ApplicationWindowImpl for MainWindowTemplate {}
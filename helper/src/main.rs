@@ -2,17 +2,36 @@ use evdev::{Device, EventType};
 use std::process::Command;
 use std::env;
 use anyhow::Result;
+use asus_control_protocol::{PROTOCOL_VERSION, hello, init_logging};
+use tracing::{info, warn};
+
+/// Sends `hello` to the daemon and warns if the GUI won't be able to talk to it.
+fn check_daemon() {
+    match hello() {
+        Err(e) => warn!("asus-control-daemon is not running ({})", e),
+        Ok(info) if !info.is_current() => {
+            warn!(
+                "{} speaks protocol {}, the GUI needs {}",
+                info.daemon, info.protocol, PROTOCOL_VERSION
+            );
+        }
+        Ok(_) => {}
+    }
+}
 
 fn main() -> Result<()> {
+    init_logging(env::args().collect());
+    check_daemon();
+
     let mut dev = Device::open("/dev/input/event10")?;
 
-    println!("Listening for MyASUS button (KEY_PROG1)...");
+    info!("Listening for MyASUS button (KEY_PROG1)...");
 
     loop {
         for ev in dev.fetch_events()? {
             if ev.event_type() == EventType::KEY {
                 if ev.code() == 148 && ev.value() == 1 {
-                    println!("MyASUS button pressed! Launching GUI...");
+                    info!("MyASUS button pressed! Launching GUI...");
 
                     let mut cmd = Command::new("/home/user/Projects/asus-control/gui-gtk4/target/debug/asus-control-gui");
                     cmd.envs(env::vars());
@@ -21,4 +40,4 @@ fn main() -> Result<()> {
             }
         }
     }
-}
\ No newline at end of file
+}
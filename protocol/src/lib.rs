@@ -0,0 +1,143 @@
+//! What asus-control-daemon and its clients agree on: where the socket is, which protocol
+//! they speak, and how a request and the `hello` handshake go. The `logging` feature adds
+//! the log setup the graphical clients share.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+/// The daemon protocol this build speaks. Bumped whenever a verb or target changes meaning;
+/// new targets are announced by `hello`.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const SOCKET_PATH: &str = "/run/asus-control-daemon.sock";
+
+/// Where the daemon listens; `ASUS_CONTROL_SOCKET` points at another one, e.g. a daemon
+/// started with `--simulate --socket <path>`.
+pub fn socket_path() -> PathBuf {
+    env::var_os("ASUS_CONTROL_SOCKET").map_or_else(|| PathBuf::from(SOCKET_PATH), PathBuf::from)
+}
+
+/// Sends one request to the daemon and returns its whole response.
+pub fn query(cmd: &str) -> io::Result<String> {
+    let mut stream = UnixStream::connect(socket_path())?;
+    stream.write_all(cmd.as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+/// What a daemon announced in its `hello` response.
+#[derive(Debug, PartialEq)]
+pub struct DaemonInfo {
+    pub daemon: String,
+    pub protocol: u32,
+    pub verbs: Vec<String>,
+    pub get: Vec<String>,
+    pub set: Vec<String>,
+}
+
+impl DaemonInfo {
+    /// Daemons from before the handshake only knew these commands.
+    pub fn legacy() -> DaemonInfo {
+        let words = |s: &str| s.split_whitespace().map(String::from).collect();
+        DaemonInfo {
+            daemon: "asus-control-daemon (pre-handshake)".into(),
+            protocol: 0,
+            verbs: words("get set"),
+            get: words("battery-threshold profile fan-speed-rpm"),
+            set: words("battery-threshold profile"),
+        }
+    }
+
+    pub fn parse(response: &str) -> DaemonInfo {
+        let mut info = DaemonInfo {
+            daemon: String::new(),
+            protocol: 0,
+            verbs: Vec::new(),
+            get: Vec::new(),
+            set: Vec::new(),
+        };
+        for (key, value) in response.lines().filter_map(|l| l.split_once(": ")) {
+            let words = || value.split_whitespace().map(String::from).collect();
+            match key {
+                "daemon" => info.daemon = value.to_string(),
+                "protocol" => info.protocol = value.parse().unwrap_or(0),
+                "verbs" => info.verbs = words(),
+                "get" => info.get = words(),
+                "set" => info.set = words(),
+                _ => {}
+            }
+        }
+        info
+    }
+
+    /// Whether the daemon speaks at least the protocol of this build.
+    pub fn is_current(&self) -> bool {
+        self.protocol >= PROTOCOL_VERSION
+    }
+}
+
+/// Asks the daemon what it supports, falling back to the legacy command set for daemons
+/// that predate `hello`.
+pub fn hello() -> io::Result<DaemonInfo> {
+    let response = query("hello\n")?;
+    if response.starts_with("error:") {
+        Ok(DaemonInfo::legacy())
+    } else {
+        Ok(DaemonInfo::parse(&response))
+    }
+}
+
+/// Logs warnings and errors only, unless `--log-level <filter>` or `RUST_LOG` asks for more.
+/// Returns `args` without the `--log-level` option.
+#[cfg(feature = "logging")]
+pub fn init_logging(mut args: Vec<String>) -> Vec<String> {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = match args.iter().position(|a| a == "--log-level") {
+        Some(i) => {
+            let level = args
+                .drain(i..(i + 2).min(args.len()))
+                .nth(1)
+                .unwrap_or_default();
+            EnvFilter::try_new(&level).unwrap_or_else(|e| {
+                eprintln!("Invalid log level {}: {}", level, e);
+                EnvFilter::new("warn")
+            })
+        }
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hello() {
+        let info = DaemonInfo::parse(
+            "daemon: asus-control-daemon 0.1.0\nprotocol: 1\nverbs: hello get set\n\
+             get: profile status\nset: profile\nunknown: ignored",
+        );
+        assert_eq!(
+            info,
+            DaemonInfo {
+                daemon: "asus-control-daemon 0.1.0".into(),
+                protocol: 1,
+                verbs: vec!["hello".into(), "get".into(), "set".into()],
+                get: vec!["profile".into(), "status".into()],
+                set: vec!["profile".into()],
+            }
+        );
+        assert!(info.is_current());
+        assert!(!DaemonInfo::legacy().is_current());
+        assert_eq!(DaemonInfo::parse("protocol: x").protocol, 0);
+    }
+}
@@ -6,6 +6,18 @@ if ! getent group "asus-control" >/dev/null; then
     sudo groupadd asus-control
 fi
 
+# The daemon runs as its own user, so nothing else can take its bus names or edit its state.
+if ! getent passwd "asus-control-daemon" >/dev/null; then
+    echo "Creating system user asus-control-daemon..."
+    sudo useradd --system --user-group --no-create-home --shell /usr/sbin/nologin asus-control-daemon
+fi
+if [ -d /var/lib/asus-control ]; then
+    sudo chown -R asus-control-daemon:asus-control-daemon /var/lib/asus-control
+fi
+
+echo "Installing the D-Bus policy..."
+sudo install -Dm644 "$(dirname "$0")/daemon/asus-control-daemon.conf" /usr/share/dbus-1/system.d/asus-control-daemon.conf
+
 if ! id -nG "$USER" | grep -qw "asus-control"; then
     echo "Adding $USER to group asus-control..."
     sudo usermod -aG "asus-control" "$USER"